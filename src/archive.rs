@@ -0,0 +1,61 @@
+use commands::*;
+use error::Error;
+use options::ArchiveMode;
+
+const ARCHIVES: [ArchiveMode; 2] = [ArchiveMode::Refs, ArchiveMode::Tags];
+
+// Every archived branch, along with its tip and the ref it's archived to.
+pub fn archived_branches() -> Result<Vec<String>, Error> {
+    let mut branches = vec![];
+
+    for archive in &ARCHIVES {
+        let refs = output(&[
+            "git",
+            "for-each-ref",
+            "--format=%(refname) %(objectname:short)",
+            archive.prefix(),
+        ])?;
+        for line in refs.lines() {
+            let mut pieces = line.splitn(2, ' ');
+            if let (Some(git_ref), Some(sha)) = (pieces.next(), pieces.next()) {
+                let branch = git_ref.trim_start_matches(archive.prefix());
+                branches.push(format!("{} ({}, {})", branch, sha, git_ref));
+            }
+        }
+    }
+
+    Ok(branches)
+}
+
+// Recreates an archived branch and drops its archive, which is looked for under `refs/archive/`
+// first and `archive/` tags second.
+pub fn restore(branch: &str) -> Result<String, Error> {
+    let mut archived = None;
+    for git_ref in ARCHIVES.iter().map(|a| a.git_ref(branch)) {
+        // `rev-parse --verify --quiet` fails without a message when the ref doesn't exist.
+        let sha_cmd = run_command(&["git", "rev-parse", "--verify", "--quiet", &git_ref])?;
+        let sha = String::from_utf8_lossy(&sha_cmd.stdout).trim().to_owned();
+        if sha_cmd.status.success() && !sha.is_empty() {
+            archived = Some((git_ref, sha));
+            break;
+        }
+    }
+
+    let (git_ref, sha) = match archived {
+        Some(archived) => archived,
+        None => return Ok(format!("Couldn't find an archive of {}.", branch)),
+    };
+
+    let restore_cmd = create_ref(&format!("refs/heads/{}", branch), &sha)?;
+    if !restore_cmd.status.success() {
+        return Ok(format!("Couldn't restore {}, it already exists.", branch));
+    }
+    run_command_with_no_output(&["git", "update-ref", "-d", &git_ref, &sha])?;
+
+    Ok(format!(
+        "Restored {} from {} (was {}).",
+        branch,
+        git_ref,
+        short_sha(&sha)
+    ))
+}
@@ -1,214 +1,971 @@
 use commands::*;
 use error::Error;
+use journal::Entry;
 use options::*;
-use regex::Regex;
+use picker;
+use std::collections::BTreeMap;
 use std::io::{stdin, stdout, Write};
 
 pub const COLUMN_SPACER_LENGTH: usize = 30;
 
+// Why a branch or a tag was picked for deletion, which is what the prompt groups them by and plans
+// describe them with.
+pub trait SelectionReason {
+    fn description(&self) -> String;
+
+    // Whether deleting it can lose work.
+    fn is_risky(&self) -> bool;
+
+    // Where its group goes in the prompt, safest first.
+    fn rank(&self) -> u8;
+
+    fn heading(&self) -> String {
+        let description = self.description();
+        let mut heading = description[..1].to_uppercase() + &description[1..];
+        if self.is_risky() {
+            heading.push_str(", may hold unmerged work");
+        }
+        heading + ":"
+    }
+}
+
+// Why a branch was picked for deletion.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Reason {
+    Merged(String),
+    Squashed(String),
+    Rebased(String),
+    Gone,
+    Unpushed,
+    Stale(u64),
+}
+
+impl SelectionReason for Reason {
+    fn description(&self) -> String {
+        match *self {
+            Reason::Merged(ref base) => format!("merged into {}", base),
+            Reason::Squashed(ref base) => format!("squash-merged into {}", base),
+            Reason::Rebased(ref base) => format!("rebase-merged into {}", base),
+            Reason::Gone => "upstream branch is gone".to_owned(),
+            Reason::Unpushed => "not on the remote".to_owned(),
+            Reason::Stale(days) => format!("no commits in over {} days", days),
+        }
+    }
+
+    // Only merged branches are known to have all their work on a base branch, deleting any of the
+    // others can lose work.
+    fn is_risky(&self) -> bool {
+        match *self {
+            Reason::Merged(_) | Reason::Squashed(_) | Reason::Rebased(_) => false,
+            Reason::Gone | Reason::Unpushed | Reason::Stale(_) => true,
+        }
+    }
+
+    fn rank(&self) -> u8 {
+        match *self {
+            Reason::Merged(_) => 0,
+            Reason::Squashed(_) => 1,
+            Reason::Rebased(_) => 2,
+            Reason::Gone => 3,
+            Reason::Unpushed => 4,
+            Reason::Stale(_) => 5,
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct Branch {
+    pub name: String,
+    pub reason: Reason,
+    // The tip of the local branch, or of the remote one when it only exists there.
+    pub sha: String,
+    pub local: bool,
+    // The branch on the remote that gets deleted along with this one, which doesn't have to have
+    // the same name.
+    pub remote: Option<String>,
+    // A linked worktree that has to be removed before the branch can be deleted.
+    pub worktree: Option<String>,
+}
+
+impl Branch {
+    // Names the branch along with its tip, and the remote branch when it's named differently.
+    fn label(&self, remote: &str) -> String {
+        match (self.local, &self.remote) {
+            (false, Some(remote_branch)) => {
+                format!("{}/{} ({})", remote, remote_branch, short_sha(&self.sha))
+            }
+            (true, Some(remote_branch)) if *remote_branch != self.name => format!(
+                "{} ({}, {}/{})",
+                self.name,
+                short_sha(&self.sha),
+                remote,
+                remote_branch
+            ),
+            _ => format!("{} ({})", self.name, short_sha(&self.sha)),
+        }
+    }
+
+    // The local branch, or the remote-tracking one when it only exists on the remote.
+    fn git_ref(&self, remote: &str) -> String {
+        match (self.local, &self.remote) {
+            (false, Some(remote_branch)) => format!("refs/remotes/{}/{}", remote, remote_branch),
+            _ => format!("refs/heads/{}", self.name),
+        }
+    }
+}
+
 #[derive(Debug)]
 pub struct Branches {
-    pub string: String,
-    pub vec: Vec<String>,
+    pub vec: Vec<Branch>,
 }
 
 impl Branches {
-    pub fn new(branches: Vec<String>) -> Branches {
-        let trimmed_string = branches.join("\n").trim_end_matches('\n').into();
+    pub fn new(branches: Vec<Branch>) -> Branches {
+        Branches { vec: branches }
+    }
 
-        Branches {
-            string: trimmed_string,
-            vec: branches,
-        }
+    pub fn is_empty(&self) -> bool {
+        self.vec.is_empty()
     }
 
-    pub fn print_warning_and_prompt(&self, delete_mode: &DeleteMode) -> Result<(), Error> {
-        println!("{}", delete_mode.warning_message());
-        println!("{}", self.format_columns());
-        print!("Continue? (Y/n) ");
-        stdout().flush()?;
+    pub fn print_warning_and_prompt(&self, options: &Options) -> Result<(), Error> {
+        let warning = options.warning_message();
+        prompt(&warning, &self.groups(&options.remote))
+    }
 
-        // Read the user's response on continuing
-        let mut input = String::new();
-        stdin().read_line(&mut input)?;
+    // Lets the user pick which of the branches to delete from a full-screen list, grouped the same
+    // way the prompt groups them, and drops the rest.
+    pub fn pick(&mut self, options: &Options) -> Result<(), Error> {
+        self.sort_by_reason();
+        let items = self
+            .vec
+            .iter()
+            .map(|b| picker::Item {
+                label: b.label(&options.remote),
+                heading: b.reason.heading(),
+                reason: b.reason.description(),
+                git_ref: b.git_ref(&options.remote),
+            })
+            .collect();
+
+        let mut picked = picker::pick(&options.warning_message(), items)?.into_iter();
+        self.vec.retain(|_| picked.next().unwrap_or(false));
+        Ok(())
+    }
 
-        match input.to_lowercase().as_ref() {
-            "y\n" | "y\r\n" | "yes\n" | "yes\r\n" | "\n" | "\r\n" => Ok(()),
-            _ => Err(Error::ExitEarly),
+    // Asks about each of the branches in turn, safest first, and drops the ones the user keeps.
+    pub fn step_through(&mut self, options: &Options) -> Result<(), Error> {
+        self.sort_by_reason();
+        let bases = options
+            .base_branches()?
+            .into_iter()
+            .map(|b| b.git_ref)
+            .collect::<Vec<String>>();
+
+        println!("{}", options.warning_message());
+        let mut confirmed = vec![];
+        let mut rest = None;
+        let mut heading = String::new();
+        for branch in &self.vec {
+            let delete = match rest {
+                Some(delete) => delete,
+                None => {
+                    if branch.reason.heading() != heading {
+                        heading = branch.reason.heading();
+                        println!("\n{}", heading);
+                    }
+                    let answer = ask(branch, &options.remote, &bases)?;
+                    if answer == Answer::All || answer == Answer::Quit {
+                        rest = Some(answer == Answer::All);
+                    }
+                    answer == Answer::Yes || answer == Answer::All
+                }
+            };
+            confirmed.push(delete);
         }
+
+        let mut confirmed = confirmed.into_iter();
+        self.vec.retain(|_| confirmed.next().unwrap_or(false));
+        Ok(())
+    }
+
+    // Keeps the branches in the order the prompt groups them in.
+    fn sort_by_reason(&mut self) {
+        self.vec
+            .sort_by_key(|b| (b.reason.rank(), b.reason.heading()));
     }
 
-    pub fn merged(options: &Options) -> Branches {
-        let mut branches: Vec<String> = vec![];
+    // The branches grouped by why they were picked, safest first.
+    fn groups(&self, remote: &str) -> Vec<(String, Vec<String>)> {
+        group_by_reason(
+            self.vec
+                .iter()
+                .map(|b| (&b.reason, b.label(remote)))
+                .collect(),
+        )
+    }
+
+    pub fn merged(options: &Options) -> Result<Branches, Error> {
+        let mut branches: Vec<(String, Reason)> = vec![];
         println!("Updating remote {}", options.remote);
-        run_command_with_no_output(&["git", "remote", "update", &options.remote, "--prune"]);
-
-        let merged_branches_regex = format!("^\\*?\\s*{}$", options.base_branch);
-        let merged_branches_filter = Regex::new(&merged_branches_regex).unwrap();
-        let merged_branches_cmd = run_command(&["git", "branch", "--merged"]);
-        let merged_branches_output = std::str::from_utf8(&merged_branches_cmd.stdout).unwrap();
-
-        let merged_branches =
-            merged_branches_output
-                .lines()
-                .fold(Vec::<String>::new(), |mut acc, line| {
-                    if !merged_branches_filter.is_match(line) {
-                        acc.push(line.trim().to_string());
-                    }
-                    acc
-                });
+        // Branches can still be cleaned from what was fetched last, remote deletions are leased on
+        // the SHAs we saw, so nobody's newer work is lost.
+        if let Err(e) =
+            run_command_with_no_output(&["git", "remote", "update", &options.remote, "--prune"])
+        {
+            println!("{}\nUsing the branches from the last fetch.", e);
+        }
 
-        let local_branches_regex = format!("^\\*?\\s*{}$", options.base_branch);
-        let local_branches_filter = Regex::new(&local_branches_regex).unwrap();
-        let local_branches_cmd = run_command(&["git", "branch"]);
-        let local_branches_output = std::str::from_utf8(&local_branches_cmd.stdout).unwrap();
+        // Comparing against the base branches' refs instead of HEAD means we don't care which
+        // branch is checked out, but we can't delete that one, so it's protected like the bases.
+        let base_branches = options.base_branches()?;
+        let current_branch = current_branch()?;
 
-        let local_branches = local_branches_output
-            .lines()
-            .fold(Vec::<String>::new(), |mut acc, line| {
-                if !local_branches_filter.is_match(line) {
-                    acc.push(line.trim().to_string());
+        // Branches checked out in any worktree can't be deleted either, unless we were asked to
+        // remove clean linked worktrees along with their merged branch.
+        let worktrees = worktrees()?;
+        let mut removable_worktrees = BTreeMap::new();
+        for worktree in &worktrees {
+            if let Some(ref branch) = worktree.branch {
+                if options.prune_worktrees
+                    && Some(branch) != current_branch.as_ref()
+                    && worktree.is_removable()?
+                {
+                    removable_worktrees.insert(branch.clone(), worktree.path.clone());
                 }
-                acc
-            })
-            .iter()
-            .filter(|branch| !options.ignored_branches.contains(branch))
-            .cloned()
+            }
+        }
+        let checked_out = worktrees
+            .into_iter()
+            .filter_map(|w| w.branch)
             .collect::<Vec<String>>();
 
-        let remote_branches_regex = format!("\\b(HEAD|{})\\b", &options.base_branch);
-        let remote_branches_filter = Regex::new(&remote_branches_regex).unwrap();
-        let remote_branches_cmd = run_command(&["git", "branch", "-r"]);
-        let remote_branches_output = std::str::from_utf8(&remote_branches_cmd.stdout).unwrap();
-
-        let remote_branches =
-            remote_branches_output
-                .lines()
-                .fold(Vec::<String>::new(), |mut acc, line| {
-                    if !remote_branches_filter.is_match(line) {
-                        acc.push(line.trim().to_string());
-                    }
-                    acc
-                });
+        let protected = |branch: &String| {
+            options.is_protected(branch)
+                || Some(branch) == current_branch.as_ref()
+                || (checked_out.contains(branch) && !removable_worktrees.contains_key(branch))
+        };
+
+        let merged_branches = merged_into_bases(&base_branches, "refs/heads", 2)?;
+
+        // Protected branches, including those outside --include, are dropped before any of the
+        // per-branch checks below, so scoped runs stay fast on repos with lots of branches.
+        let local_branches = local_branches()?
+            .into_iter()
+            .filter(|branch| !protected(branch))
+            .collect::<Vec<String>>();
+        let remote_branches = remote_branches(&options.remote)?;
+
+        let gone_branches = if options.gone {
+            gone_branches(&options.remote)?
+        } else {
+            vec![]
+        };
 
         for branch in local_branches {
-            // First check if the local branch doesn't exist in the remote, it's the cheapest and easiest
-            // way to determine if we want to suggest to delete it.
-            if options.delete_unpushed_branches
-                && !remote_branches
-                    .iter()
-                    .any(|b: &String| *b == format!("{}/{}", &options.remote, branch))
-            {
-                branches.push(branch.to_owned());
+            // The full ref, so a tag with the same name isn't checked instead.
+            if let Some(reason) = merged_reason(
+                &format!("refs/heads/{}", branch),
+                merged_branches.get(&branch),
+                &base_branches,
+                options,
+            )? {
+                branches.push((branch, reason));
                 continue;
             }
 
-            // If it does exist in the remote, check to see if it's listed in git branches --merged. If
-            // it is, that means it wasn't merged using Github squashes, and we can suggest it.
-            if merged_branches.iter().any(|b: &String| *b == branch) {
-                branches.push(branch.to_owned());
-                continue;
+            // Branches that aren't on the remote, or whose upstream was deleted from it, which
+            // usually happens when its PR gets merged, are suggested too. Merge checks go first,
+            // so a squash-merged PR branch is known to be safe instead of being refused.
+            if options.delete_unpushed_branches && !remote_branches.contains(&branch) {
+                branches.push((branch, Reason::Unpushed));
+            } else if gone_branches.contains(&branch) {
+                branches.push((branch, Reason::Gone));
             }
+        }
+
+        let upstreams = upstream_branches(&options.remote)?;
+        let local_shas = ref_values("refs/heads", 2, "objectname")?;
+        let remote_shas = ref_values(&format!("refs/remotes/{}", options.remote), 3, "objectname")?;
+
+        let mut merged = branches
+            .into_iter()
+            .map(|(name, reason)| {
+                // A local branch can track a remote branch with a different name, in which case
+                // that's the one we want to delete.
+                let remote_branch = upstreams.get(&name).unwrap_or(&name);
+                Branch {
+                    sha: local_shas.get(&name).cloned().unwrap_or_default(),
+                    local: true,
+                    remote: remote_shas
+                        .get(remote_branch)
+                        .filter(|_| !options.is_protected(remote_branch))
+                        .map(|_| remote_branch.to_owned()),
+                    worktree: removable_worktrees.get(&name).cloned(),
+                    name,
+                    reason,
+                }
+            })
+            .collect::<Vec<Branch>>();
+
+        // Merged branches on the remote that we never checked out, or whose local branch isn't
+        // merged, can only be found by looking at the remote-tracking refs themselves.
+        if options.sweep_remotes {
+            let swept = swept_remote_branches(options, &base_branches, &merged, &remote_shas)?;
+            merged.extend(swept);
+        }
+
+        if let Some(days) = options.older_than {
+            let stale = stale_branches(options, days, &merged, &checked_out)?;
+            merged.extend(stale);
+        }
 
-            // If neither of the above matched, merge main into the branch and see if it succeeds.
-            // If it can't cleanly merge, then it has likely been merged with Github squashes, and we
-            // can suggest it.
-            if options.squashes {
-                run_command(&["git", "checkout", &branch]);
-                match run_command_with_status(&[
-                    "git",
-                    "pull",
-                    "--ff-only",
-                    &options.remote,
-                    &options.base_branch,
-                ]) {
-                    Ok(status) => {
-                        if !status.success() {
-                            println!("why");
-                            branches.push(branch);
-                        }
+        // On shared repos, other people's remote branches are theirs to delete. Local branches are
+        // always ours, so those are still deleted locally.
+        if let Some(ref author) = options.author {
+            let base_refs = base_branches
+                .iter()
+                .map(|b| b.git_ref.as_str())
+                .collect::<Vec<&str>>();
+            for branch in &mut merged {
+                let authored = match branch.remote {
+                    Some(ref remote_branch) => {
+                        let remote_ref =
+                            format!("refs/remotes/{}/{}", options.remote, remote_branch);
+                        is_authored_by(&remote_ref, &base_refs, author)?
                     }
-                    Err(err) => {
-                        println!(
-                            "Encountered error trying to update branch {} with branch {}: {}",
-                            branch, options.base_branch, err
-                        );
-                        continue;
+                    None => false,
+                };
+                if !authored {
+                    branch.remote = None;
+                }
+            }
+            merged.retain(|b| b.local || b.remote.is_some());
+        }
+
+        Ok(Branches::new(merged))
+    }
+
+    // Drops every branch holding commits that no ref we keep has, since `git branch -D` and remote
+    // deletions would lose them for good. Merged branches never hold any, and squashed or rebased
+    // ones have their changes on a base under other SHAs, so only the risky ones are checked.
+    pub fn refuse_unmerged(&mut self, options: &Options) -> Result<String, Error> {
+        let remote_shas = ref_values(&format!("refs/remotes/{}", options.remote), 3, "objectname")?;
+
+        // Every ref about to be deleted is left out of the refs the commits have to be on.
+        let mut tips: Vec<Vec<String>> = vec![];
+        let mut excludes = vec![];
+        for branch in &self.vec {
+            let mut branch_tips = vec![];
+            if branch.local && options.delete_mode.deletes_local() {
+                branch_tips.push(branch.sha.clone());
+                excludes.push(format!("--exclude=refs/heads/{}", branch.name));
+            }
+            if let Some(ref remote_branch) = branch.remote {
+                if let Some(sha) = remote_shas.get(remote_branch) {
+                    if options.delete_mode.deletes_remote() {
+                        branch_tips.push(sha.clone());
+                        excludes.push(format!(
+                            "--exclude=refs/remotes/{}/{}",
+                            options.remote, remote_branch
+                        ));
                     }
                 }
+            }
+            tips.push(branch_tips);
+        }
+
+        let mut refusals = vec![];
+        let mut kept = vec![];
+        for (branch, branch_tips) in self.vec.drain(..).zip(tips) {
+            if !branch.reason.is_risky() || branch_tips.is_empty() {
+                kept.push(branch);
+                continue;
+            }
 
-                run_command(&["git", "reset", "--hard"]);
-                run_command(&["git", "checkout", &options.base_branch]);
+            let mut log_args = vec!["git", "log", "--format=%h %s"];
+            log_args.extend(branch_tips.iter().map(|t| t.as_str()));
+            log_args.push("--not");
+            log_args.extend(excludes.iter().map(|e| e.as_str()));
+            log_args.push("--all");
+            let lost = output(&log_args)?;
+
+            if lost.is_empty() {
+                kept.push(branch);
+            } else {
+                let count = lost.lines().count();
+                refusals.push(format!(
+                    "Refusing to delete {}, it has {} {} nothing else has (use --force to delete it anyway):",
+                    branch.name,
+                    count,
+                    if count == 1 { "commit" } else { "commits" }
+                ));
+                refusals.extend(lost.lines().map(|commit| format!("  {}", commit)));
             }
         }
 
-        // if deleted in remote, list
-        //
-        // g branch -d -r <remote>/<branch>
-        // g branch -d <branch>
+        self.vec = kept;
+        Ok(refusals.join("\n"))
+    }
+
+    // Copies every branch to its archive ref before it gets deleted, locally and, with
+    // --archive-remote, on the remote too. Branches that can't be archived aren't deleted either.
+    pub fn archive(&mut self, options: &Options, archive: &ArchiveMode) -> Result<String, Error> {
+        let remote_shas = ref_values(&format!("refs/remotes/{}", options.remote), 3, "objectname")?;
+        let mut output = vec![];
+        let mut archived = vec![];
+
+        for mut branch in self.vec.drain(..) {
+            let remote_sha = branch
+                .remote
+                .as_ref()
+                .and_then(|remote_branch| remote_shas.get(remote_branch))
+                .cloned();
+            let sha = if branch.local && options.delete_mode.deletes_local() {
+                branch.sha.clone()
+            } else {
+                remote_sha.clone().unwrap_or_else(|| branch.sha.clone())
+            };
+
+            let git_ref = archive.git_ref(&branch.name);
+            let archive_cmd = create_ref(&git_ref, &sha)?;
+            if !archive_cmd.status.success() {
+                output.push(format!(
+                    "Couldn't archive {}, {} already exists.",
+                    branch.name, git_ref
+                ));
+                continue;
+            }
+            output.push(format!("Archived {} to {}", branch.name, git_ref));
+
+            if options.archive_remote && options.delete_mode.deletes_remote() {
+                if let (Some(remote_branch), Some(remote_sha)) = (branch.remote.clone(), remote_sha)
+                {
+                    let remote_ref = archive.git_ref(&remote_branch);
+                    let archive_remote_cmd = run_command(&[
+                        "git",
+                        "push",
+                        &format!("--force-with-lease={}:", remote_ref),
+                        &options.remote,
+                        &format!("{}:{}", remote_sha, remote_ref),
+                    ])?;
+                    if archive_remote_cmd.status.success() {
+                        output.push(format!(
+                            "Archived {}/{} to {}",
+                            options.remote, remote_branch, remote_ref
+                        ));
+                    } else {
+                        output.push(format!(
+                            "Couldn't archive {}/{}, {} already exists on the remote.",
+                            options.remote, remote_branch, remote_ref
+                        ));
+                        branch.remote = None;
+                    }
+                }
+            }
 
-        Branches::new(branches)
+            archived.push(branch);
+        }
+
+        self.vec = archived;
+        Ok(output.join("\n"))
     }
 
-    fn format_columns(&self) -> String {
-        // Covers the single column case
-        if self.vec.len() < 26 {
-            return self.string.clone();
+    // Writes every local branch and remote-tracking branch `delete` is about to remove to a git
+    // bundle, and checks the bundle can be read back before anything gets deleted.
+    pub fn bundle(&self, options: &Options, path: &str) -> Result<String, Error> {
+        let remote_shas = ref_values(&format!("refs/remotes/{}", options.remote), 3, "objectname")?;
+        let mut refs = vec![];
+
+        for branch in &self.vec {
+            if branch.local && options.delete_mode.deletes_local() {
+                refs.push(format!("refs/heads/{}", branch.name));
+            }
+            if let Some(ref remote_branch) = branch.remote {
+                if remote_shas.contains_key(remote_branch) && options.delete_mode.deletes_remote() {
+                    refs.push(format!("refs/remotes/{}/{}", options.remote, remote_branch));
+                }
+            }
         }
 
-        let col_count = {
-            let total_cols = self.vec.len() / 25 + 1;
-            ::std::cmp::min(total_cols, 3)
-        };
+        if refs.is_empty() {
+            return Ok(String::default());
+        }
 
-        let chunks = self.vec.chunks(col_count);
-        let mut col_indices = [0; 3];
+        let bundle_args = refs
+            .iter()
+            .fold(vec!["git", "bundle", "create", path], |mut acc, r| {
+                acc.push(r);
+                acc
+            });
+        let bundle_cmd = run_command(&bundle_args)?;
+        let verify_cmd = run_command(&["git", "bundle", "verify", path])?;
+        if !bundle_cmd.status.success() || !verify_cmd.status.success() {
+            return Err(Error::BundleFailed(path.to_owned()));
+        }
 
-        for i in 1..col_count {
-            let index = i - 1;
-            let largest_col_member = chunks
-                .clone()
-                .map(|chunk| {
-                    if let Some(branch) = chunk.get(index) {
-                        branch.len()
-                    } else {
-                        0
+        Ok(format!("Bundled {} refs to {}", refs.len(), path))
+    }
+
+    // The refs `delete` is about to remove, what they point to and why they were picked, for plans
+    // and the undo journal.
+    pub fn refs_to_delete(&self, options: &Options) -> Result<Vec<(Entry, &Reason)>, Error> {
+        let remote_shas = ref_values(&format!("refs/remotes/{}", options.remote), 3, "objectname")?;
+        let mut entries = vec![];
+
+        for branch in &self.vec {
+            if branch.local && options.delete_mode.deletes_local() {
+                entries.push((
+                    Entry::local(format!("refs/heads/{}", branch.name), branch.sha.clone()),
+                    &branch.reason,
+                ));
+            }
+            if let Some(ref remote_branch) = branch.remote {
+                if let Some(sha) = remote_shas.get(remote_branch) {
+                    if options.delete_mode.deletes_remote() && !options.is_protected(remote_branch)
+                    {
+                        entries.push((
+                            Entry::remote(
+                                &options.remote,
+                                format!("refs/heads/{}", remote_branch),
+                                sha.clone(),
+                            ),
+                            &branch.reason,
+                        ));
                     }
-                })
-                .max()
-                .unwrap();
-            let next_col_start = largest_col_member + COLUMN_SPACER_LENGTH;
-            col_indices[i - 1] = next_col_start;
+                }
+            }
         }
 
-        let rows: Vec<String> = self
-            .vec
-            .chunks(col_count)
-            .map(|chunk| make_row(chunk, &col_indices))
-            .collect();
+        // A ref picked for two reasons is still only deleted once, git refuses to push the same
+        // deletion twice.
+        let mut seen = vec![];
+        entries.retain(|(entry, _)| {
+            let key = (entry.location.clone(), entry.git_ref.clone());
+            let first = !seen.contains(&key);
+            seen.push(key);
+            first
+        });
+
+        Ok(entries)
+    }
+}
+
+// Lists the groups of things about to be deleted under the warning, and asks whether to go on.
+pub fn prompt(warning: &str, groups: &[(String, Vec<String>)]) -> Result<(), Error> {
+    println!("{}", warning);
+    for (heading, labels) in groups {
+        println!("\n{}", heading);
+        println!("{}", format_columns(labels));
+    }
+    print!("\nContinue? (Y/n) ");
+    stdout().flush()?;
+
+    // Read the user's response on continuing
+    let mut input = String::new();
+    stdin().read_line(&mut input)?;
+
+    match input.to_lowercase().as_ref() {
+        "y\n" | "y\r\n" | "yes\n" | "yes\r\n" | "\n" | "\r\n" => Ok(()),
+        _ => Err(Error::ExitEarly),
+    }
+}
+
+// An answer to the question whether to delete a branch, with git's `add -p` letters.
+#[derive(Debug, PartialEq)]
+enum Answer {
+    Yes,
+    No,
+    // Delete this branch and all the remaining ones, or keep them all.
+    All,
+    Quit,
+    Log,
+    Help,
+}
 
-        rows.join("\n").trim().to_owned()
-    }
-
-    pub fn delete(&self, options: &Options) -> String {
-        match options.delete_mode {
-            DeleteMode::Local => delete_local_branches(self),
-            DeleteMode::Remote => delete_remote_branches(self, options),
-            DeleteMode::Both => {
-                let local_output = delete_local_branches(self);
-                let remote_output = delete_remote_branches(self, options);
-                [
-                    "Remote:".to_owned(),
-                    remote_output,
-                    "\nLocal:".to_owned(),
-                    local_output,
-                ]
-                .join("\n")
+const STEP_HELP: &str = "y - delete this branch
+n - keep this branch
+a - delete this branch and all the remaining ones
+q - keep this branch and all the remaining ones
+l - show the commits it has that the base branches don't
+? - print help";
+
+impl Answer {
+    fn parse(input: &str) -> Option<Answer> {
+        match input.trim().to_lowercase().as_ref() {
+            "y" | "yes" => Some(Answer::Yes),
+            "n" | "no" => Some(Answer::No),
+            "a" | "all" => Some(Answer::All),
+            "q" | "quit" => Some(Answer::Quit),
+            "l" | "log" => Some(Answer::Log),
+            "?" => Some(Answer::Help),
+            _ => None,
+        }
+    }
+}
+
+// Asks whether to delete `branch` until it gets an answer that settles it, showing its log or the
+// help in between. Running out of input aborts, like the `Continue?` prompt.
+fn ask(branch: &Branch, remote: &str, bases: &[String]) -> Result<Answer, Error> {
+    loop {
+        print!("Delete {}? [y,n,a,q,l,?] ", branch.label(remote));
+        stdout().flush()?;
+
+        let mut input = String::new();
+        if stdin().read_line(&mut input)? == 0 {
+            return Err(Error::ExitEarly);
+        }
+
+        match Answer::parse(&input) {
+            Some(Answer::Log) => {
+                let git_ref = branch.git_ref(remote);
+                let mut args = vec!["git", "log", "--format=%h %s", &git_ref, "--not"];
+                args.extend(bases.iter().map(|b| b.as_str()));
+                let log = output(&args)?;
+                if log.is_empty() {
+                    println!("Every commit is on a base branch already.");
+                } else {
+                    println!("{}", log);
+                }
             }
+            Some(Answer::Help) | None => println!("{}", STEP_HELP),
+            Some(answer) => return Ok(answer),
         }
     }
 }
 
+// Groups labels under the heading of the reason they were picked for, safest reasons first.
+pub fn group_by_reason<R: SelectionReason>(
+    mut labels: Vec<(&R, String)>,
+) -> Vec<(String, Vec<String>)> {
+    labels.sort_by_key(|&(reason, _)| (reason.rank(), reason.heading()));
+
+    let mut groups: Vec<(String, Vec<String>)> = vec![];
+    for (reason, label) in labels {
+        let heading = reason.heading();
+        match groups.last_mut() {
+            Some(&mut (ref last_heading, ref mut labels)) if *last_heading == heading => {
+                labels.push(label)
+            }
+            _ => groups.push((heading, vec![label])),
+        }
+    }
+    groups
+}
+
+// Checks whether `branch`, which can be any ref, was merged into one of the base branches, and
+// how. `merged_into` is the base `git for-each-ref --merged` already found it in, if any.
+fn merged_reason(
+    branch: &str,
+    merged_into: Option<&String>,
+    base_branches: &[BaseBranch],
+    options: &Options,
+) -> Result<Option<Reason>, Error> {
+    // If it's listed in git branches --merged, that means it wasn't merged using Github squashes,
+    // and we can suggest it.
+    if let Some(base) = merged_into {
+        return Ok(Some(Reason::Merged(base.clone())));
+    }
+
+    // If it isn't, check whether the branch's changes were squashed into a single commit on a base
+    // branch, like Github squashes do.
+    if options.squashes {
+        for base in base_branches {
+            if is_squash_merged(branch, &base.git_ref)? {
+                return Ok(Some(Reason::Squashed(base.name.clone())));
+            }
+        }
+    }
+
+    // Github's "Rebase and merge" replays every commit onto the base branch with a new SHA, so look
+    // for a patch-equivalent commit on a base branch for each of them.
+    if options.rebases {
+        for base in base_branches {
+            if is_rebase_merged(branch, &base.git_ref)? {
+                return Ok(Some(Reason::Rebased(base.name.clone())));
+            }
+        }
+    }
+
+    Ok(None)
+}
+
+// Maps every branch under `prefix` that's merged into one of the bases to the first base it was
+// merged into, so they can be told apart in the listing.
+fn merged_into_bases(
+    base_branches: &[BaseBranch],
+    prefix: &str,
+    strip: usize,
+) -> Result<BTreeMap<String, String>, Error> {
+    let mut merged_branches: BTreeMap<String, String> = BTreeMap::new();
+    for base in base_branches {
+        let merged_into_base = output(&[
+            "git",
+            "for-each-ref",
+            &format!("--format=%(refname:lstrip={})", strip),
+            "--merged",
+            &base.git_ref,
+            prefix,
+        ])?;
+        for branch in merged_into_base.lines() {
+            merged_branches
+                .entry(branch.to_owned())
+                .or_insert_with(|| base.name.clone());
+        }
+    }
+    Ok(merged_branches)
+}
+
+// Merged branches on the remote that aren't already being deleted along with a local branch.
+fn swept_remote_branches(
+    options: &Options,
+    base_branches: &[BaseBranch],
+    candidates: &[Branch],
+    remote_shas: &BTreeMap<String, String>,
+) -> Result<Vec<Branch>, Error> {
+    let prefix = format!("refs/remotes/{}", options.remote);
+    let merged_branches = merged_into_bases(base_branches, &prefix, 3)?;
+    let mut swept = vec![];
+
+    for (branch, sha) in remote_shas {
+        if branch == "HEAD"
+            || options.is_protected(branch)
+            || candidates.iter().any(|b| b.remote.as_ref() == Some(branch))
+        {
+            continue;
+        }
+
+        let remote_ref = format!("{}/{}", prefix, branch);
+        if let Some(reason) = merged_reason(
+            &remote_ref,
+            merged_branches.get(branch),
+            base_branches,
+            options,
+        )? {
+            swept.push(Branch {
+                name: branch.to_owned(),
+                reason,
+                sha: sha.to_owned(),
+                local: false,
+                remote: Some(branch.to_owned()),
+                worktree: None,
+            });
+        }
+    }
+
+    Ok(swept)
+}
+
+// Local branches that track a branch on `remote` which no longer exists there. `git remote update
+// --prune` has to run first so the remote-tracking refs are up to date.
+fn gone_branches(remote: &str) -> Result<Vec<String>, Error> {
+    let refs = output(&[
+        "git",
+        "for-each-ref",
+        "--format=%(refname:lstrip=2) %(upstream:remotename) %(upstream:track)",
+        "refs/heads",
+    ])?;
+
+    Ok(refs
+        .lines()
+        .filter_map(|line| {
+            let mut pieces = line.splitn(3, ' ');
+            match (pieces.next(), pieces.next(), pieces.next()) {
+                (Some(branch), Some(upstream_remote), Some("[gone]"))
+                    if upstream_remote == remote =>
+                {
+                    Some(branch.to_owned())
+                }
+                _ => None,
+            }
+        })
+        .collect())
+}
+
+// Branches whose newest tip, locally and on the remote, is older than `days`. A local branch and its
+// remote counterpart are treated as one branch, so neither side gets deleted while the other one is
+// still being worked on.
+fn stale_branches(
+    options: &Options,
+    days: u64,
+    merged: &[Branch],
+    checked_out: &[String],
+) -> Result<Vec<Branch>, Error> {
+    let cutoff = now().saturating_sub(days * 24 * 60 * 60);
+    let is_stale = |date: Option<&u64>| date.is_none_or(|date| *date < cutoff);
+
+    let upstreams = upstream_branches(&options.remote)?;
+    let local_dates = commit_dates("refs/heads", 2)?;
+    let remote_dates = commit_dates(&format!("refs/remotes/{}", options.remote), 3)?;
+    let local_shas = ref_values("refs/heads", 2, "objectname")?;
+    let remote_shas = ref_values(&format!("refs/remotes/{}", options.remote), 3, "objectname")?;
+    let protected = |branch: &String| options.is_protected(branch) || checked_out.contains(branch);
+    // Remote branches the sweep already picked are deleted along with those, never twice.
+    let picked = |remote_branch: &String| {
+        merged
+            .iter()
+            .any(|b| b.remote.as_ref() == Some(remote_branch))
+    };
+
+    let mut stale = vec![];
+    let mut counterparts = vec![];
+
+    for (branch, date) in &local_dates {
+        let remote_branch = upstreams.get(branch).unwrap_or(branch);
+        counterparts.push(remote_branch.to_owned());
+
+        if protected(branch) || merged.iter().any(|b| b.name == *branch) {
+            continue;
+        }
+
+        let remote_date = remote_dates.get(remote_branch);
+        if let Remote = options.delete_mode {
+            if remote_date.is_none() {
+                continue;
+            }
+        }
+
+        if is_stale(Some(date)) && is_stale(remote_date) {
+            stale.push(Branch {
+                name: branch.to_owned(),
+                reason: Reason::Stale(days),
+                sha: local_shas.get(branch).cloned().unwrap_or_default(),
+                local: true,
+                remote: remote_date
+                    .filter(|_| !options.is_protected(remote_branch) && !picked(remote_branch))
+                    .map(|_| remote_branch.to_owned()),
+                worktree: None,
+            });
+        }
+    }
+
+    if let Local = options.delete_mode {
+        return Ok(stale);
+    }
+
+    for (branch, date) in &remote_dates {
+        if branch == "HEAD" || protected(branch) || counterparts.contains(branch) || picked(branch)
+        {
+            continue;
+        }
+
+        if is_stale(Some(date)) {
+            stale.push(Branch {
+                name: branch.to_owned(),
+                reason: Reason::Stale(days),
+                sha: remote_shas.get(branch).cloned().unwrap_or_default(),
+                local: false,
+                remote: Some(branch.to_owned()),
+                worktree: None,
+            });
+        }
+    }
+
+    Ok(stale)
+}
+
+// Maps every branch under `prefix` to the unix timestamp of its tip commit.
+fn commit_dates(prefix: &str, strip: usize) -> Result<BTreeMap<String, u64>, Error> {
+    Ok(ref_values(prefix, strip, "committerdate:unix")?
+        .into_iter()
+        .filter_map(|(branch, date)| date.parse().ok().map(|date| (branch, date)))
+        .collect())
+}
+
+// Whether every commit on `branch` that isn't on a base branch was authored by `author`, as
+// mapped by `.mailmap`. Branches that are fully merged don't have any of those left, so their tip
+// commit decides.
+fn is_authored_by(branch: &str, base_refs: &[&str], author: &str) -> Result<bool, Error> {
+    let mut log_args = vec!["git", "log", "--format=%aE", branch, "--not"];
+    log_args.extend(base_refs);
+    let mut authors = output(&log_args)?;
+    if authors.is_empty() {
+        authors = output(&["git", "log", "-1", "--format=%aE", branch])?;
+    }
+
+    Ok(!authors.is_empty()
+        && authors
+            .lines()
+            .all(|email| email.eq_ignore_ascii_case(author)))
+}
+
+// Github squashes create an entirely new commit on the base branch, so the branch's own commits
+// never show up there. Instead of checking the branch out, we build a throwaway commit holding the
+// branch's net diff against the merge-base (`git commit-tree`), and ask `git cherry` whether the
+// base branch already has a commit with the same patch. Nothing here touches HEAD, the index or
+// any branch.
+fn is_squash_merged(branch: &str, base_ref: &str) -> Result<bool, Error> {
+    // `merge-base` fails without a message when the two have no history in common.
+    let merge_base_cmd = run_command(&["git", "merge-base", base_ref, branch])?;
+    let merge_base = String::from_utf8_lossy(&merge_base_cmd.stdout)
+        .trim()
+        .to_owned();
+    if !merge_base_cmd.status.success() || merge_base.is_empty() {
+        return Ok(false);
+    }
+
+    let tree = output(&["git", "rev-parse", &format!("{}^{{tree}}", branch)])?;
+    let squashed_commit = output(&[
+        "git",
+        "commit-tree",
+        &tree,
+        "-p",
+        &merge_base,
+        "-m",
+        &format!("git-clean squash check for {}", branch),
+    ])?;
+
+    // `git cherry` prefixes commits that already have an equivalent on the upstream with a `-`.
+    Ok(output(&["git", "cherry", base_ref, &squashed_commit])?.starts_with('-'))
+}
+
+// `git cherry` lists every commit on the branch that isn't on the base branch, prefixed with a `-`
+// when the base branch already has a commit with the same patch, and a `+` when it doesn't.
+fn is_rebase_merged(branch: &str, base_ref: &str) -> Result<bool, Error> {
+    let cherry = output(&["git", "cherry", base_ref, branch])?;
+
+    Ok(!cherry.is_empty() && cherry.lines().all(|line| line.starts_with('-')))
+}
+
+fn format_columns(branches: &[String]) -> String {
+    // Covers the single column case
+    if branches.len() < 26 {
+        return branches.join("\n");
+    }
+
+    let col_count = {
+        let total_cols = branches.len() / 25 + 1;
+        ::std::cmp::min(total_cols, 3)
+    };
+
+    let chunks = branches.chunks(col_count);
+    let mut col_indices = [0; 3];
+
+    for i in 1..col_count {
+        let index = i - 1;
+        let largest_col_member = chunks
+            .clone()
+            .map(|chunk| {
+                if let Some(branch) = chunk.get(index) {
+                    branch.len()
+                } else {
+                    0
+                }
+            })
+            .max()
+            .unwrap();
+        let next_col_start = largest_col_member + COLUMN_SPACER_LENGTH;
+        col_indices[i - 1] = next_col_start;
+    }
+
+    let rows: Vec<String> = branches
+        .chunks(col_count)
+        .map(|chunk| make_row(chunk, &col_indices))
+        .collect();
+
+    rows.join("\n").trim().to_owned()
+}
+
 fn make_row(chunks: &[String], col_indices: &[usize]) -> String {
     match chunks.len() {
         1 => chunks[0].clone(),
@@ -236,20 +993,109 @@ fn make_row(chunks: &[String], col_indices: &[usize]) -> String {
 
 #[cfg(test)]
 mod test {
-    use super::Branches;
+    use super::{format_columns, Answer, Branch, Branches, Reason, SelectionReason};
+
+    fn branch(name: &str, reason: Reason) -> Branch {
+        Branch {
+            name: name.to_owned(),
+            reason,
+            sha: "3a9ea97ffd3e5e8fe1cb3c44ec4ee4d6bbc1b9cd".to_owned(),
+            local: true,
+            remote: Some(name.to_owned()),
+            worktree: None,
+        }
+    }
 
     #[test]
     fn test_branches_new() {
-        let input = vec!["branch1".to_owned(), "branch2".to_owned()];
+        let input = vec![
+            branch("branch1", Reason::Merged("main".to_owned())),
+            branch("branch2", Reason::Gone),
+        ];
         let branches = Branches::new(input);
 
-        assert_eq!("branch1\nbranch2".to_owned(), branches.string);
         assert_eq!(
-            vec!["branch1".to_owned(), "branch2".to_owned()],
-            branches.vec
+            vec!["branch1", "branch2"],
+            branches
+                .vec
+                .iter()
+                .map(|b| b.name.as_str())
+                .collect::<Vec<&str>>()
+        );
+        assert!(!branches.is_empty());
+        assert!(Branches::new(vec![]).is_empty());
+    }
+
+    #[test]
+    fn test_risky_reasons() {
+        assert!(!Reason::Merged("main".to_owned()).is_risky());
+        assert!(!Reason::Squashed("main".to_owned()).is_risky());
+        assert!(!Reason::Rebased("main".to_owned()).is_risky());
+        assert!(Reason::Gone.is_risky());
+        assert!(Reason::Unpushed.is_risky());
+        assert!(Reason::Stale(90).is_risky());
+    }
+
+    #[test]
+    fn test_branches_are_grouped_by_reason() {
+        let mut renamed = branch("renamed", Reason::Merged("main".to_owned()));
+        renamed.remote = Some("upstream".to_owned());
+        let mut remote_only = branch("remote_only", Reason::Stale(90));
+        remote_only.local = false;
+        let mut local_only = branch("local_only", Reason::Unpushed);
+        local_only.remote = None;
+
+        let branches = Branches::new(vec![
+            remote_only,
+            branch("merged", Reason::Merged("main".to_owned())),
+            local_only,
+            branch("squashed", Reason::Squashed("main".to_owned())),
+            renamed,
+            branch("release_fix", Reason::Merged("release/1.0".to_owned())),
+        ]);
+
+        assert_eq!(
+            vec![
+                (
+                    "Merged into main:".to_owned(),
+                    vec![
+                        "merged (3a9ea97)".to_owned(),
+                        "renamed (3a9ea97, origin/upstream)".to_owned()
+                    ]
+                ),
+                (
+                    "Merged into release/1.0:".to_owned(),
+                    vec!["release_fix (3a9ea97)".to_owned()]
+                ),
+                (
+                    "Squash-merged into main:".to_owned(),
+                    vec!["squashed (3a9ea97)".to_owned()]
+                ),
+                (
+                    "Not on the remote, may hold unmerged work:".to_owned(),
+                    vec!["local_only (3a9ea97)".to_owned()]
+                ),
+                (
+                    "No commits in over 90 days, may hold unmerged work:".to_owned(),
+                    vec!["origin/remote_only (3a9ea97)".to_owned()]
+                ),
+            ],
+            branches.groups("origin")
         );
     }
 
+    #[test]
+    fn test_step_answers() {
+        assert_eq!(Some(Answer::Yes), Answer::parse("y\n"));
+        assert_eq!(Some(Answer::No), Answer::parse("No\r\n"));
+        assert_eq!(Some(Answer::All), Answer::parse("a\n"));
+        assert_eq!(Some(Answer::Quit), Answer::parse("quit\n"));
+        assert_eq!(Some(Answer::Log), Answer::parse(" l \n"));
+        assert_eq!(Some(Answer::Help), Answer::parse("?\n"));
+        assert_eq!(None, Answer::parse("\n"));
+        assert_eq!(None, Answer::parse("maybe\n"));
+    }
+
     #[test]
     fn test_format_single_column() {
         let mut input = vec![];
@@ -257,8 +1103,6 @@ mod test {
             input.push("branch".to_owned())
         }
 
-        let branches = Branches::new(input);
-
         let expected = "\
 branch
 branch
@@ -287,7 +1131,7 @@ branch
 branch
 branch";
 
-        assert_eq!(expected, branches.format_columns());
+        assert_eq!(expected, format_columns(&input));
     }
 
     #[test]
@@ -297,8 +1141,6 @@ branch";
             input.push("branch".to_owned())
         }
 
-        let branches = Branches::new(input);
-
         let expected = "\
 branch                              branch
 branch                              \
@@ -320,7 +1162,7 @@ branch                              \
 branch
 branch                              branch";
 
-        assert_eq!(expected, branches.format_columns());
+        assert_eq!(expected, format_columns(&input));
     }
 
     #[test]
@@ -330,8 +1172,6 @@ branch                              branch";
             input.push("branch".to_owned())
         }
 
-        let branches = Branches::new(input);
-
         let expected = "\
 branch                              branch                              \
 branch
@@ -368,7 +1208,7 @@ branch
 branch                              branch                              \
 branch";
 
-        assert_eq!(expected, branches.format_columns());
+        assert_eq!(expected, format_columns(&input));
     }
 
     #[test]
@@ -378,8 +1218,6 @@ branch";
             input.push("branch".to_owned())
         }
 
-        let branches = Branches::new(input);
-
         let expected = "\
 branch                              branch                              \
 branch
@@ -433,7 +1271,7 @@ branch                              branch                              \
 branch
 branch";
 
-        assert_eq!(expected, branches.format_columns());
+        assert_eq!(expected, format_columns(&input));
     }
 
     #[test]
@@ -443,8 +1281,6 @@ branch";
             input.push(format!("branch{}", i))
         }
 
-        let branches = Branches::new(input);
-
         let expected = "\
 branch0                               branch1
 branch2                               \
@@ -465,7 +1301,7 @@ branch20                              branch21
 branch22                              \
 branch23
 branch24                              branch25";
-        assert_eq!(expected, branches.format_columns());
+        assert_eq!(expected, format_columns(&input));
     }
 
     #[test]
@@ -475,8 +1311,6 @@ branch24                              branch25";
             input.push(format!("branch{}", i));
         }
 
-        let branches = Branches::new(input);
-
         let expected = "\
 really_long_branch_name                              branch-1
 branch0                                              \
@@ -499,7 +1333,7 @@ branch21
 branch22                                             branch23
 branch24                                             \
 branch25";
-        assert_eq!(expected, branches.format_columns());
+        assert_eq!(expected, format_columns(&input));
     }
 
     #[test]
@@ -516,8 +1350,6 @@ branch25";
             input.push(format!("branch{}", i));
         }
 
-        let branches = Branches::new(input);
-
         let expected = "\
 really_long_branch_name                              branch                                              branch
 branch                                               really_long_middle_col                              branch
@@ -536,6 +1368,6 @@ branch33                                             branch34
 branch36                                             branch37                                            branch38
 branch39                                             branch40                                            branch41
 branch42                                             branch43                                            branch44";
-        assert_eq!(expected, branches.format_columns());
+        assert_eq!(expected, format_columns(&input));
     }
 }
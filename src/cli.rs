@@ -1,4 +1,6 @@
-use clap::{App, Arg};
+use clap::{App, Arg, ArgGroup, SubCommand};
+use options::parse_age;
+use pattern::Pattern;
 
 const VERSION: &str = env!("CARGO_PKG_VERSION");
 
@@ -27,13 +29,130 @@ pub fn build_cli() -> App<'static, 'static> {
                 .help("Skip the check for deleting branches")
                 .takes_value(false),
         )
+        .arg(
+            Arg::with_name("interactive")
+                .long("interactive")
+                .help("Pick the branches to delete from a full-screen list, instead of the check for deleting branches")
+                .takes_value(false)
+                .conflicts_with_all(&["yes", "tags", "plan"]),
+        )
+        .arg(
+            Arg::with_name("step")
+                .long("step")
+                .help("Ask about each branch in turn, instead of the check for deleting all of them")
+                .takes_value(false)
+                .conflicts_with_all(&["yes", "tags", "plan", "interactive"]),
+        )
         .arg(
             Arg::with_name("squashes")
                 .short("s")
                 .long("squashes")
-                .help("Check for squashes by finding branches incompatible with main")
+                .help("Check for squashes by finding branches whose changes are already on the base branch")
+                .takes_value(false),
+        )
+        .arg(
+            Arg::with_name("rebases")
+                .long("rebases")
+                .help("Check for rebase merges by finding branches whose commits are all already on the base branch")
                 .takes_value(false),
         )
+        .arg(
+            Arg::with_name("gone")
+                .short("g")
+                .long("gone")
+                .help("Delete local branches whose upstream branch no longer exists on the remote")
+                .takes_value(false),
+        )
+        .arg(
+            Arg::with_name("prune-worktrees")
+                .short("w")
+                .long("prune-worktrees")
+                .help("Remove clean linked worktrees along with their merged branch, instead of skipping those branches")
+                .takes_value(false),
+        )
+        .arg(
+            Arg::with_name("sweep-remotes")
+                .long("sweep-remotes")
+                .help("Also delete merged remote branches that don't have a merged local branch")
+                .takes_value(false)
+                .conflicts_with("locals"),
+        )
+        .arg(
+            Arg::with_name("mine")
+                .short("m")
+                .long("mine")
+                .help("Only delete remote branches whose own commits were all authored by you (user.email, respecting .mailmap)")
+                .takes_value(false),
+        )
+        .arg(
+            Arg::with_name("author")
+                .long("author")
+                .help("Only delete remote branches whose own commits were all authored by the given email, instead of user.email")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("older-than")
+                .long("older-than")
+                .help("Also delete branches whose last commit is older than the given age, like 90d, 12w, 6m or 1y")
+                .takes_value(true)
+                .validator(|age| match parse_age(&age) {
+                    Some(_) => Ok(()),
+                    None => Err(format!("'{}' isn't an age like 90d, 12w, 6m or 1y", age)),
+                }),
+        )
+        .arg(
+            Arg::with_name("tags")
+                .long("tags")
+                .help("Clean tags matching the given pattern instead of branches, like ci-build-* (repeat option for multiple patterns, needs --older-than or --release-tags)")
+                .takes_value(true)
+                .multiple(true)
+                .validator(|pattern| Pattern::validate(&pattern))
+                .requires("tag-criteria"),
+        )
+        .arg(
+            Arg::with_name("release-tags")
+                .long("release-tags")
+                .help("With --tags, delete tags already contained in a kept tag matching the given pattern, like v* (repeat option for multiple patterns)")
+                .takes_value(true)
+                .multiple(true)
+                .validator(|pattern| Pattern::validate(&pattern))
+                .requires("tags"),
+        )
+        .arg(
+            Arg::with_name("archive")
+                .long("archive")
+                .help("Archive branches to refs/archive/<branch>, or to archive/<branch> tags, before deleting them")
+                .takes_value(true)
+                .possible_values(&["refs", "tags"])
+                .conflicts_with("tags"),
+        )
+        .arg(
+            Arg::with_name("bundle")
+                .long("bundle")
+                .help("Back up the branches about to be deleted, local and remote, to a git bundle at the given path first")
+                .takes_value(true)
+                .conflicts_with("tags"),
+        )
+        .arg(
+            Arg::with_name("plan")
+                .long("plan")
+                .help("Write the refs that would be deleted, with their SHAs and why, to a JSON plan for git-clean apply instead of deleting them")
+                .takes_value(true)
+                .conflicts_with_all(&["archive", "bundle"]),
+        )
+        .arg(
+            Arg::with_name("archive-remote")
+                .long("archive-remote")
+                .help("Archive remote branches on the remote too")
+                .takes_value(false)
+                .requires("archive")
+                .conflicts_with("locals"),
+        )
+        .group(
+            ArgGroup::with_name("tag-criteria")
+                .args(&["older-than", "release-tags"])
+                .multiple(true),
+        )
         .arg(
             Arg::with_name("delete-unpushed-branches")
                 .short("d")
@@ -41,6 +160,13 @@ pub fn build_cli() -> App<'static, 'static> {
                 .help("Delete any local branch that is not present on the remote. Use this to speed up the checks if such branches should always be considered as merged")
                 .takes_value(false),
         )
+        .arg(
+            Arg::with_name("force")
+                .long("force")
+                .help("Delete branches even when they hold commits that no kept branch, tag or remote branch has")
+                .takes_value(false)
+                .conflicts_with("tags"),
+        )
         .arg(
             Arg::with_name("remote")
                 .short("R")
@@ -52,15 +178,71 @@ pub fn build_cli() -> App<'static, 'static> {
             Arg::with_name("branch")
                 .short("b")
                 .long("branch")
-                .help("Changes the base for merged branches (default is main)")
-                .takes_value(true),
+                .help("Changes the base for merged branches, globs like release/* work too (repeat option for multiple bases, default is the remote's HEAD, or main)")
+                .takes_value(true)
+                .multiple(true)
+                .validator(|pattern| Pattern::validate(&pattern)),
         )
         .arg(
             Arg::with_name("ignore")
                 .short("i")
                 .long("ignore")
-                .help("Ignore given branch (repeat option for multiple branches)")
+                .help("Ignore given branch, globs like release/* and hotfix/** or regexes like ^keep-.*$ work too (repeat option for multiple branches)")
                 .takes_value(true)
-                .multiple(true),
+                .multiple(true)
+                .validator(|pattern| Pattern::validate(&pattern)),
+        )
+        .arg(
+            Arg::with_name("include")
+                .long("include")
+                .help("Only clean branches matching the given pattern, like feature/* (repeat option for multiple patterns)")
+                .takes_value(true)
+                .multiple(true)
+                .validator(|pattern| Pattern::validate(&pattern)),
+        )
+        .subcommand(
+            SubCommand::with_name("undo")
+                .about("Restores the branches and tags deleted by the latest run, or the given one")
+                .arg(
+                    Arg::with_name("run")
+                        .help("The number of the run to undo, as listed by --list")
+                        .takes_value(true),
+                )
+                .arg(
+                    Arg::with_name("list")
+                        .short("l")
+                        .long("list")
+                        .help("List the runs that can be undone")
+                        .takes_value(false)
+                        .conflicts_with("run"),
+                )
+                .arg(
+                    Arg::with_name("bundle")
+                        .long("bundle")
+                        .help("Restore the branches in a bundle written by --bundle instead")
+                        .takes_value(true)
+                        .conflicts_with_all(&["run", "list"]),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("apply")
+                .about("Deletes the refs in a plan written by --plan, unless they moved since")
+                .arg(
+                    Arg::with_name("plan")
+                        .help("The plan to apply")
+                        .takes_value(true)
+                        .required(true),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("archived")
+                .about("Lists archived branches, or restores them")
+                .arg(
+                    Arg::with_name("restore")
+                        .long("restore")
+                        .help("Restore the given archived branch (repeat option for multiple branches)")
+                        .takes_value(true)
+                        .multiple(true),
+                ),
         )
 }
@@ -1,41 +1,251 @@
-use std::collections::BTreeSet;
-use std::io::Error as IOError;
-use std::process::{Command, ExitStatus, Output, Stdio};
+use std::collections::BTreeMap;
+use std::process::{Command, Output, Stdio};
+use std::time::{SystemTime, UNIX_EPOCH};
 
 use branches::Branches;
 use error::Error;
-use options::Options;
+use journal::Entry;
+use report::{Outcome, Report};
 
-pub fn run_command_with_no_output(args: &[&str]) {
+// Runs a command for its effect alone, failing when it does.
+pub fn run_command_with_no_output(args: &[&str]) -> Result<(), Error> {
+    output(args).map(|_| ())
+}
+
+// Runs a command that has to succeed, and returns its trimmed stdout.
+pub fn output(args: &[&str]) -> Result<String, Error> {
+    let result = run_command(args)?;
+    if !result.status.success() {
+        return Err(command_error(args, &result));
+    }
+    Ok(String::from_utf8_lossy(&result.stdout).trim().to_owned())
+}
+
+// Runs a command whether or not it succeeds, for the callers that handle failures themselves.
+// Only failing to start it at all is an error.
+pub fn run_command(args: &[&str]) -> Result<Output, Error> {
     Command::new(args[0])
         .args(&args[1..])
         .stdin(Stdio::null())
-        .stdout(Stdio::null())
-        .stderr(Stdio::null())
         .output()
-        .unwrap_or_else(|e| panic!("Error with command: {}", e));
+        .map_err(|e| Error::GitCommand(to_argv(args), None, e.to_string()))
 }
 
-pub fn output(args: &[&str]) -> String {
-    let result = run_command(args);
-    String::from_utf8(result.stdout).unwrap().trim().to_owned()
+// The error for a command that ran, but failed.
+pub fn command_error(args: &[&str], result: &Output) -> Error {
+    Error::GitCommand(
+        to_argv(args),
+        Some(result.status),
+        String::from_utf8_lossy(&result.stderr).trim().to_owned(),
+    )
 }
 
-pub fn run_command(args: &[&str]) -> Output {
-    run_command_with_result(args).unwrap_or_else(|e| panic!("Error with command: {}", e))
+fn to_argv(args: &[&str]) -> Vec<String> {
+    args.iter().map(|a| (*a).to_owned()).collect()
 }
 
-pub fn run_command_with_result(args: &[&str]) -> Result<Output, IOError> {
-    Command::new(args[0]).args(&args[1..]).output()
+// The abbreviated SHA git prints in its own messages.
+pub fn short_sha(sha: &str) -> &str {
+    &sha[..::std::cmp::min(7, sha.len())]
 }
 
-pub fn run_command_with_status(args: &[&str]) -> Result<ExitStatus, IOError> {
-    Command::new(args[0])
-        .args(&args[1..])
-        .stdin(Stdio::null())
-        .stdout(Stdio::null())
-        .stderr(Stdio::null())
-        .status()
+// Seconds since the Unix epoch.
+pub fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
+
+// Points `git_ref` at `sha` unless it already exists, since an empty old value makes `update-ref`
+// refuse to overwrite an existing ref.
+pub fn create_ref(git_ref: &str, sha: &str) -> Result<Output, Error> {
+    run_command(&["git", "update-ref", git_ref, sha, ""])
+}
+
+// The names of all local branches.
+pub fn local_branches() -> Result<Vec<String>, Error> {
+    let branches = output(&[
+        "git",
+        "for-each-ref",
+        "--format=%(refname:lstrip=2)",
+        "refs/heads",
+    ])?;
+    Ok(branches.lines().map(|line| line.to_owned()).collect())
+}
+
+// The names of all branches on `remote` as of the last fetch, without the `<remote>/` prefix.
+pub fn remote_branches(remote: &str) -> Result<Vec<String>, Error> {
+    let branches = output(&[
+        "git",
+        "for-each-ref",
+        "--format=%(refname:lstrip=3)",
+        &format!("refs/remotes/{}", remote),
+    ])?;
+    Ok(branches
+        .lines()
+        .filter(|line| *line != "HEAD")
+        .map(|line| line.to_owned())
+        .collect())
+}
+
+// Every local ref, with what it points to.
+pub fn local_refs() -> Result<BTreeMap<String, String>, Error> {
+    let refs = output(&["git", "for-each-ref", "--format=%(refname) %(objectname)"])?;
+    Ok(refs
+        .lines()
+        .filter_map(|line| {
+            let mut pieces = line.splitn(2, ' ');
+            match (pieces.next(), pieces.next()) {
+                (Some(git_ref), Some(sha)) => Some((git_ref.to_owned(), sha.to_owned())),
+                _ => None,
+            }
+        })
+        .collect())
+}
+
+// Every ref under `prefix` on `remote`, with what it points to. The remote is asked directly,
+// since not every ref has a remote-tracking counterpart.
+pub fn remote_refs(remote: &str, prefix: &str) -> Result<BTreeMap<String, String>, Error> {
+    let refs = output(&[
+        "git",
+        "ls-remote",
+        "--refs",
+        remote,
+        &format!("{}/*", prefix),
+    ])?;
+    Ok(refs
+        .lines()
+        .filter_map(|line| {
+            let mut pieces = line.split('\t');
+            match (pieces.next(), pieces.next()) {
+                (Some(sha), Some(git_ref)) => Some((git_ref.to_owned(), sha.to_owned())),
+                _ => None,
+            }
+        })
+        .collect())
+}
+
+// Maps every branch under `prefix` to the given `git for-each-ref` field, stripping `strip`
+// components off the ref name.
+pub fn ref_values(
+    prefix: &str,
+    strip: usize,
+    field: &str,
+) -> Result<BTreeMap<String, String>, Error> {
+    let refs = output(&[
+        "git",
+        "for-each-ref",
+        &format!("--format=%(refname:lstrip={}) %({})", strip, field),
+        prefix,
+    ])?;
+
+    Ok(refs
+        .lines()
+        .filter_map(|line| {
+            let mut pieces = line.splitn(2, ' ');
+            match (pieces.next(), pieces.next()) {
+                (Some(branch), Some(value)) => Some((branch.to_owned(), value.to_owned())),
+                _ => None,
+            }
+        })
+        .collect())
+}
+
+#[derive(Debug)]
+pub struct Worktree {
+    pub path: String,
+    pub branch: Option<String>,
+    pub linked: bool,
+    pub locked: bool,
+    pub prunable: bool,
+}
+
+impl Worktree {
+    // Linked worktrees can be removed with their branch, as long as that doesn't throw away any
+    // changes. `git worktree remove` refuses dirty worktrees too, but we want to know up front.
+    pub fn is_removable(&self) -> Result<bool, Error> {
+        if !self.linked || self.locked || self.prunable {
+            return Ok(false);
+        }
+
+        // A worktree git can't even look at isn't one we can tell is clean.
+        let status_cmd = run_command(&["git", "-C", &self.path, "status", "--porcelain"])?;
+        Ok(status_cmd.status.success() && status_cmd.stdout.is_empty())
+    }
+}
+
+// Every worktree of the repository, starting with the main one.
+pub fn worktrees() -> Result<Vec<Worktree>, Error> {
+    let porcelain = output(&["git", "worktree", "list", "--porcelain"])?;
+    let mut worktrees: Vec<Worktree> = vec![];
+
+    for line in porcelain.lines() {
+        if let Some(path) = line.strip_prefix("worktree ") {
+            let linked = !worktrees.is_empty();
+            worktrees.push(Worktree {
+                path: path.to_owned(),
+                branch: None,
+                linked,
+                locked: false,
+                prunable: false,
+            });
+        } else if let Some(worktree) = worktrees.last_mut() {
+            if let Some(branch) = line.strip_prefix("branch refs/heads/") {
+                worktree.branch = Some(branch.to_owned());
+            } else if line.starts_with("locked") {
+                worktree.locked = true;
+            } else if line.starts_with("prunable") {
+                worktree.prunable = true;
+            }
+        }
+    }
+
+    Ok(worktrees)
+}
+
+// The checked out branch, or `None` when HEAD is detached.
+pub fn current_branch() -> Result<Option<String>, Error> {
+    // `symbolic-ref --quiet` fails without a message when HEAD is detached.
+    let branch_cmd = run_command(&["git", "symbolic-ref", "--quiet", "--short", "HEAD"])?;
+    let branch = String::from_utf8_lossy(&branch_cmd.stdout)
+        .trim()
+        .to_owned();
+
+    if !branch_cmd.status.success() || branch.is_empty() {
+        Ok(None)
+    } else {
+        Ok(Some(branch))
+    }
+}
+
+// Maps every local branch that tracks a branch on `remote` to the name of that remote branch, which
+// doesn't have to match the name of the local branch.
+pub fn upstream_branches(remote: &str) -> Result<BTreeMap<String, String>, Error> {
+    let refs = output(&[
+        "git",
+        "for-each-ref",
+        "--format=%(refname:lstrip=2) %(upstream:remotename) %(upstream:remoteref)",
+        "refs/heads",
+    ])?;
+
+    Ok(refs
+        .lines()
+        .filter_map(|line| {
+            let mut pieces = line.split(' ');
+            match (pieces.next(), pieces.next(), pieces.next()) {
+                (Some(branch), Some(upstream_remote), Some(upstream_ref))
+                    if upstream_remote == remote && upstream_ref.starts_with("refs/heads/") =>
+                {
+                    Some((
+                        branch.to_owned(),
+                        upstream_ref.trim_start_matches("refs/heads/").to_owned(),
+                    ))
+                }
+                _ => None,
+            }
+        })
+        .collect())
 }
 
 pub fn validate_git_installation() -> Result<(), Error> {
@@ -45,86 +255,158 @@ pub fn validate_git_installation() -> Result<(), Error> {
     }
 }
 
-pub fn delete_local_branches(branches: &Branches) -> String {
-    // https://git-scm.com/docs/git-branch
-    // With a -d or -D option, <branchname> will be deleted. You may specify more than one branch
-    // for deletion.
-    //
-    // So we can work without xargs.
-    if branches.vec.is_empty() {
-        String::default()
-    } else {
-        let delete_branches_args =
-            branches
-                .vec
-                .iter()
-                .fold(vec!["git", "branch", "-D"], |mut acc, b| {
-                    acc.push(b);
-                    acc
-                });
-        let delete_branches_cmd = run_command(&delete_branches_args);
-        String::from_utf8(delete_branches_cmd.stdout).unwrap()
+// Removes the linked worktrees of the local branches about to be deleted, since git can't delete
+// a branch that's checked out anywhere.
+pub fn remove_worktrees(branches: &Branches) -> Result<String, Error> {
+    let mut removed_worktrees = vec![];
+    for branch in branches.vec.iter().filter(|b| b.local) {
+        if let Some(ref path) = branch.worktree {
+            output(&["git", "worktree", "remove", path])?;
+            removed_worktrees.push(format!("Removed worktree {}", path));
+        }
     }
+    Ok(removed_worktrees.join("\n"))
 }
 
-pub fn delete_remote_branches(branches: &Branches, options: &Options) -> String {
-    let remote_branches_cmd = run_command(&["git", "branch", "-r"]);
+// Deletes every ref, locally and on its remote, and reports what happened to each of them.
+pub fn delete_refs(entries: &[Entry]) -> Result<Report, Error> {
+    let mut remotes: BTreeMap<&str, Vec<&Entry>> = BTreeMap::new();
+    for entry in entries.iter().filter(|e| !e.is_local()) {
+        remotes.entry(&entry.location).or_default().push(entry);
+    }
 
-    let s = String::from_utf8(remote_branches_cmd.stdout).unwrap();
-    let all_remote_branches = s.split('\n').collect::<Vec<&str>>();
-    let origin_for_trim = &format!("{}/", &options.remote)[..];
-    let b_tree_remotes = all_remote_branches
-        .iter()
-        .map(|b| b.trim().trim_start_matches(origin_for_trim).to_owned())
-        .collect::<BTreeSet<String>>();
+    let mut refs = vec![];
+    for (remote, remote_entries) in remotes {
+        // A push that fails as a whole, like to an unreachable remote, fails each of its refs.
+        let outcomes = match push_deletions(remote, &remote_entries) {
+            Ok(outcomes) => outcomes,
+            Err(e) => remote_entries
+                .iter()
+                .map(|_| Outcome::Failed(e.to_string()))
+                .collect(),
+        };
+        refs.extend(remote_entries.into_iter().cloned().zip(outcomes));
+    }
 
-    let mut b_tree_branches = BTreeSet::new();
+    // `git branch -D` also drops the branch's config, like its upstream, which `update-ref` wouldn't.
+    // Each ref gets its own command, so one that can't be deleted doesn't hide the others.
+    for entry in entries.iter().filter(|e| e.is_local()) {
+        let delete_args = if let Some(tag) = entry.git_ref.strip_prefix("refs/tags/") {
+            vec!["git", "tag", "-d", tag]
+        } else {
+            vec![
+                "git",
+                "branch",
+                "-D",
+                entry.git_ref.trim_start_matches("refs/heads/"),
+            ]
+        };
+        let delete_cmd = run_command(&delete_args)?;
+        let stderr = String::from_utf8_lossy(&delete_cmd.stderr)
+            .trim()
+            .to_owned();
 
-    for branch in branches.vec.clone() {
-        b_tree_branches.insert(branch);
+        // git's messages are translated, so whether the ref was already gone is asked separately.
+        let outcome = if delete_cmd.status.success() {
+            Outcome::Deleted
+        } else if !ref_exists(&entry.git_ref)? {
+            Outcome::AlreadyGone
+        } else {
+            Outcome::Failed(stderr)
+        };
+        refs.push((entry.clone(), outcome));
     }
 
-    let intersection: Vec<_> = b_tree_remotes
-        .intersection(&b_tree_branches)
-        .cloned()
-        .collect();
+    Ok(Report::new(refs))
+}
 
-    let stderr = if intersection.is_empty() {
-        String::default()
-    } else {
-        let delete_branches_args = intersection.iter().fold(
-            vec!["git", "push", &options.remote, "--delete"],
-            |mut acc, b| {
-                acc.push(b);
-                acc
-            },
-        );
-        let delete_remote_branches_cmd = run_command(&delete_branches_args);
-        String::from_utf8(delete_remote_branches_cmd.stderr).unwrap()
-    };
+// Whether `git_ref` exists locally. `rev-parse --verify --quiet` fails without a message when it
+// doesn't.
+fn ref_exists(git_ref: &str) -> Result<bool, Error> {
+    Ok(
+        run_command(&["git", "rev-parse", "--verify", "--quiet", git_ref])?
+            .status
+            .success(),
+    )
+}
 
-    // Everything is written to stderr, so we need to process that
-    let split = stderr.split('\n');
-    let vec: Vec<&str> = split.collect();
-    let mut output = vec![];
-    for s in vec {
-        if s.contains("error: unable to delete '") {
-            let branch = s
-                .trim_start_matches("error: unable to delete '")
-                .trim_end_matches("': remote ref does not exist");
-
-            output.push(branch.to_owned() + " was already deleted in the remote.");
-        } else if s.contains(" - [deleted]") {
-            output.push(s.to_owned());
+// Deletes refs on `remote`, each with a lease on the SHA it was seen at, so a ref someone pushed
+// to since then is left alone instead of taking their work with it. Returns the outcome of each
+// ref, in order.
+// Maps every remote ref in the output of `git push --porcelain`, which prints
+// `<flag>\t<from>:<to>\t<summary>` to stdout for every ref it pushed, to its flag and summary.
+pub fn porcelain_results(stdout: &str) -> BTreeMap<&str, (&str, &str)> {
+    let mut pushed = BTreeMap::new();
+    for line in stdout.lines() {
+        let mut pieces = line.split('\t');
+        if let (Some(flag), Some(refspec), Some(summary)) =
+            (pieces.next(), pieces.next(), pieces.next())
+        {
+            if let Some(git_ref) = refspec.rsplit(':').next() {
+                pushed.insert(git_ref, (flag, summary));
+            }
         }
     }
+    pushed
+}
 
-    output.join("\n")
+fn push_deletions(remote: &str, entries: &[&Entry]) -> Result<Vec<Outcome>, Error> {
+    let leases = entries
+        .iter()
+        .map(|e| format!("--force-with-lease={}:{}", e.git_ref, e.sha))
+        .collect::<Vec<String>>();
+    let mut delete_args = vec!["git", "push", "--porcelain"];
+    delete_args.extend(leases.iter().map(|l| l.as_str()));
+    delete_args.extend(&[remote, "--delete"]);
+    delete_args.extend(entries.iter().map(|e| e.git_ref.as_str()));
+    let delete_cmd = run_command(&delete_args)?;
+    let stdout = String::from_utf8_lossy(&delete_cmd.stdout);
+    let pushed = porcelain_results(&stdout);
+
+    // Anything else, like an unreachable remote, fails the whole push.
+    let stderr = String::from_utf8_lossy(&delete_cmd.stderr);
+    if !delete_cmd.status.success() && pushed.is_empty() {
+        return Err(command_error(&delete_args, &delete_cmd));
+    }
+
+    // A lease on a ref the remote no longer has is rejected as stale too, and older versions of
+    // git refuse to delete refs the remote doesn't have up front, so the remote is asked which of
+    // those still exist.
+    let needs_check = entries
+        .iter()
+        .any(|entry| match pushed.get(entry.git_ref.as_str()) {
+            Some(&(_, summary)) => summary.contains("(stale info)"),
+            None => true,
+        });
+    let remote_refs = if needs_check {
+        remote_refs(remote, "refs")?
+    } else {
+        BTreeMap::new()
+    };
+
+    Ok(entries
+        .iter()
+        .map(|entry| match pushed.get(entry.git_ref.as_str()) {
+            Some(&("-", _)) => Outcome::Deleted,
+            Some(&(_, summary)) if summary.contains("(stale info)") => {
+                if remote_refs.contains_key(&entry.git_ref) {
+                    Outcome::Rejected("someone pushed to it since it was fetched".to_owned())
+                } else {
+                    Outcome::AlreadyGone
+                }
+            }
+            Some(&(_, summary)) => Outcome::Rejected(format!("the remote said {}", summary)),
+            None if !remote_refs.contains_key(&entry.git_ref) => Outcome::AlreadyGone,
+            None => Outcome::Failed(stderr.trim().to_owned()),
+        })
+        .collect())
 }
 
 #[cfg(test)]
 mod test {
 
+    use super::{output, run_command};
+    use error::Error;
     use regex::Regex;
 
     // `spawn_piped` was removed so this test is somewhat outdated.
@@ -141,4 +423,21 @@ mod test {
             "foo\n"
         );
     }
+
+    #[test]
+    fn test_failed_commands_are_errors() {
+        match output(&["git", "not-a-git-command"]) {
+            Err(Error::GitCommand(args, Some(status), stderr)) => {
+                assert_eq!(vec!["git", "not-a-git-command"], args);
+                assert!(!status.success());
+                assert!(stderr.contains("not-a-git-command"), "{}", stderr);
+            }
+            other => panic!("Expected a GitCommand error, but found: {:?}", other),
+        }
+
+        match run_command(&["git-clean-no-such-binary"]) {
+            Err(Error::GitCommand(_, None, _)) => (),
+            other => panic!("Expected a GitCommand error, but found: {:?}", other),
+        }
+    }
 }
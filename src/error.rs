@@ -1,18 +1,40 @@
 use std::error::Error as StdError;
 use std::fmt::{Display, Error as FmtError, Formatter};
 use std::io::Error as IoError;
+use std::process::ExitStatus;
+
+use report::{EXIT_ABORTED, EXIT_FATAL};
 
 #[derive(Debug)]
 pub enum Error {
     GitInstallation,
-    CurrentBranchInvalid,
+    InvalidBaseBranch(String),
     InvalidRemote,
+    MissingAuthor,
+    NothingToUndo,
+    BundleFailed(String),
+    InvalidBundle(String),
+    InvalidPlan(String, String),
+    UnknownRun(String),
+    NotATerminal,
+    // A git command that failed, with its arguments, how it exited (`None` when it couldn't be
+    // started at all) and what it wrote to stderr.
+    GitCommand(Vec<String>, Option<ExitStatus>, String),
     ExitEarly,
     Io(IoError),
 }
 
 use self::Error::*;
 
+impl Error {
+    pub fn exit_code(&self) -> i32 {
+        match *self {
+            ExitEarly => EXIT_ABORTED,
+            _ => EXIT_FATAL,
+        }
+    }
+}
+
 impl StdError for Error {
     fn source(&self) -> Option<&(dyn StdError + 'static)> {
         match *self {
@@ -30,10 +52,59 @@ impl Display for Error {
             GitInstallation => {
                 write!(f, "Unable to execute 'git' on your machine, please make sure it's installed and on your PATH")
             }
-            CurrentBranchInvalid => {
+            InvalidBaseBranch(ref base_branch) => {
+                write!(
+                    f,
+                    "Couldn't find the base branch {}, locally or on the remote. Please pick another one with -b.",
+                    base_branch
+                )
+            }
+            MissingAuthor => {
+                write!(
+                    f,
+                    "Couldn't tell whose branches to keep, please set user.email or pass --author."
+                )
+            }
+            NothingToUndo => {
+                write!(f, "There's nothing to undo, git-clean hasn't deleted anything in this repository yet.")
+            }
+            BundleFailed(ref bundle) => {
+                write!(
+                    f,
+                    "Couldn't write and verify the bundle {}, so nothing was deleted.",
+                    bundle
+                )
+            }
+            InvalidBundle(ref bundle) => {
+                write!(f, "Couldn't read any branches from the bundle {}.", bundle)
+            }
+            InvalidPlan(ref plan, ref reason) => {
+                write!(f, "Couldn't read the plan {}: {}", plan, reason)
+            }
+            UnknownRun(ref run) => {
+                write!(
+                    f,
+                    "Couldn't find the run {}, please pick one from git-clean undo --list.",
+                    run
+                )
+            }
+            GitCommand(ref args, Some(ref status), ref stderr) => {
+                match status.code() {
+                    Some(code) => write!(f, "`{}` failed with exit code {}", args.join(" "), code)?,
+                    None => write!(f, "`{}` was killed by a signal", args.join(" "))?,
+                }
+                if !stderr.is_empty() {
+                    write!(f, ":\n{}", stderr)?;
+                }
+                Ok(())
+            }
+            GitCommand(ref args, None, ref reason) => {
+                write!(f, "Couldn't run `{}`: {}", args.join(" "), reason)
+            }
+            NotATerminal => {
                 write!(
                     f,
-                    "Please make sure to run git-clean from your base branch (defaults to main)."
+                    "--interactive needs a terminal, please leave it out to be prompted instead."
                 )
             }
             InvalidRemote => {
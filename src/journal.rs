@@ -0,0 +1,325 @@
+use commands::*;
+use error::Error;
+use std::collections::BTreeMap;
+use std::fs::{self, OpenOptions};
+use std::io::{ErrorKind, Write};
+use std::path::PathBuf;
+
+const JOURNAL_FILE: &str = "git-clean-journal";
+const LOCAL: &str = "local";
+
+// A ref that got deleted, locally or on a remote, and what it pointed to before that.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Entry {
+    // `local`, or the remote the ref was deleted on.
+    pub location: String,
+    pub git_ref: String,
+    pub sha: String,
+}
+
+impl Entry {
+    pub fn local(git_ref: String, sha: String) -> Entry {
+        Entry {
+            location: LOCAL.to_owned(),
+            git_ref,
+            sha,
+        }
+    }
+
+    pub fn remote(remote: &str, git_ref: String, sha: String) -> Entry {
+        Entry {
+            location: remote.to_owned(),
+            git_ref,
+            sha,
+        }
+    }
+
+    pub fn is_local(&self) -> bool {
+        self.location == LOCAL
+    }
+
+    pub fn label(&self) -> String {
+        let name = if let Some(tag) = self.git_ref.strip_prefix("refs/tags/") {
+            format!("tag {}", tag)
+        } else {
+            self.git_ref.trim_start_matches("refs/heads/").to_owned()
+        };
+
+        if self.is_local() {
+            name
+        } else {
+            format!("{}/{}", self.location, name)
+        }
+    }
+}
+
+// Every ref deleted by one run of git-clean. Runs are appended to `.git/git-clean-journal`, one
+// line per ref and a blank line after each run, so they can be undone later.
+#[derive(Debug, PartialEq)]
+pub struct Run {
+    pub timestamp: u64,
+    pub entries: Vec<Entry>,
+}
+
+impl Run {
+    pub fn new(entries: Vec<Entry>) -> Run {
+        Run {
+            timestamp: now(),
+            entries,
+        }
+    }
+
+    pub fn summary(&self) -> String {
+        let labels = self
+            .entries
+            .iter()
+            .map(|e| e.label())
+            .collect::<Vec<String>>();
+        labels.join(", ")
+    }
+
+    pub fn record(self) -> Result<(), Error> {
+        if self.entries.is_empty() {
+            return Ok(());
+        }
+
+        let mut journal = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(journal_path()?)?;
+        journal.write_all(format_run(&self).as_bytes())?;
+        Ok(())
+    }
+
+    // Recreates the local refs and pushes the remote ones back. Refs that were recreated since
+    // are left alone.
+    pub fn undo(&self) -> Result<String, Error> {
+        let mut output = vec![];
+
+        for entry in self.entries.iter().filter(|e| e.is_local()) {
+            let restore_cmd = create_ref(&entry.git_ref, &entry.sha)?;
+            if restore_cmd.status.success() {
+                output.push(format!(
+                    "Restored {} (was {}).",
+                    entry.label(),
+                    short_sha(&entry.sha)
+                ));
+            } else {
+                output.push(format!(
+                    "Couldn't restore {}: {}",
+                    entry.label(),
+                    String::from_utf8_lossy(&restore_cmd.stderr).trim()
+                ));
+            }
+        }
+
+        let mut remotes: BTreeMap<&str, Vec<&Entry>> = BTreeMap::new();
+        for entry in self.entries.iter().filter(|e| !e.is_local()) {
+            remotes.entry(&entry.location).or_default().push(entry);
+        }
+
+        for (remote, entries) in remotes {
+            // Leasing on an empty value makes the push refuse to overwrite a ref someone
+            // recreated since, even when it would fast-forward.
+            let leases = entries
+                .iter()
+                .map(|e| format!("--force-with-lease={}:", e.git_ref))
+                .collect::<Vec<String>>();
+            let refspecs = entries
+                .iter()
+                .map(|e| format!("{}:{}", e.sha, e.git_ref))
+                .collect::<Vec<String>>();
+            let mut push_args = vec!["git", "push", "--porcelain"];
+            push_args.extend(leases.iter().map(|l| l.as_str()));
+            push_args.push(remote);
+            push_args.extend(refspecs.iter().map(|r| r.as_str()));
+            let push_cmd = run_command(&push_args)?;
+            let stdout = String::from_utf8_lossy(&push_cmd.stdout);
+            let pushed = porcelain_results(&stdout);
+
+            // Anything else, like an unreachable remote, fails the whole push.
+            if !push_cmd.status.success() && pushed.is_empty() {
+                return Err(command_error(&push_args, &push_cmd));
+            }
+
+            for entry in entries {
+                output.push(match pushed.get(entry.git_ref.as_str()) {
+                    Some(&("!", summary)) if summary.contains("(stale info)") => format!(
+                        "Couldn't restore {}: someone recreated it since it was deleted",
+                        entry.label()
+                    ),
+                    Some(&("!", summary)) => format!(
+                        "Couldn't restore {}: the remote said {}",
+                        entry.label(),
+                        summary
+                    ),
+                    Some(_) => format!(
+                        "Restored {} (was {}).",
+                        entry.label(),
+                        short_sha(&entry.sha)
+                    ),
+                    None => format!(
+                        "Couldn't restore {}: {}",
+                        entry.label(),
+                        String::from_utf8_lossy(&push_cmd.stderr).trim()
+                    ),
+                });
+            }
+        }
+
+        Ok(output.join("\n"))
+    }
+}
+
+// Every journaled run, oldest first.
+pub fn runs() -> Result<Vec<Run>, Error> {
+    match fs::read_to_string(journal_path()?) {
+        Ok(journal) => Ok(parse_runs(&journal)),
+        Err(ref e) if e.kind() == ErrorKind::NotFound => Ok(vec![]),
+        Err(e) => Err(Error::Io(e)),
+    }
+}
+
+// The refs in a bundle written by --bundle, as a run that restores them. Local branches are
+// recreated and remote-tracking branches pushed back to their remote, once the bundle's objects
+// are fetched.
+pub fn bundled_run(path: &str) -> Result<Run, Error> {
+    let heads = output(&["git", "bundle", "list-heads", path])
+        .map_err(|_| Error::InvalidBundle(path.to_owned()))?;
+    let mut refs = vec![];
+    let mut entries = vec![];
+
+    for line in heads.lines() {
+        let mut pieces = line.splitn(2, ' ');
+        let (sha, git_ref) = match (pieces.next(), pieces.next()) {
+            (Some(sha), Some(git_ref)) => (sha, git_ref),
+            _ => continue,
+        };
+
+        if git_ref.starts_with("refs/heads/") {
+            entries.push(Entry::local(git_ref.to_owned(), sha.to_owned()));
+        } else if let Some(remote_ref) = git_ref.strip_prefix("refs/remotes/") {
+            let mut remote_pieces = remote_ref.splitn(2, '/');
+            if let (Some(remote), Some(branch)) = (remote_pieces.next(), remote_pieces.next()) {
+                entries.push(Entry::remote(
+                    remote,
+                    format!("refs/heads/{}", branch),
+                    sha.to_owned(),
+                ));
+            }
+        } else {
+            continue;
+        }
+        refs.push(git_ref);
+    }
+
+    if entries.is_empty() {
+        return Err(Error::InvalidBundle(path.to_owned()));
+    }
+
+    let fetch_args = refs.iter().fold(vec!["git", "fetch", path], |mut acc, r| {
+        acc.push(r);
+        acc
+    });
+    if !run_command(&fetch_args)?.status.success() {
+        return Err(Error::InvalidBundle(path.to_owned()));
+    }
+
+    Ok(Run::new(entries))
+}
+
+fn journal_path() -> Result<PathBuf, Error> {
+    let git_dir = output(&["git", "rev-parse", "--git-common-dir"])?;
+    Ok(PathBuf::from(git_dir).join(JOURNAL_FILE))
+}
+
+// Each line is `<timestamp> <location> <ref> <sha>`, none of which can hold a space.
+fn format_run(run: &Run) -> String {
+    let lines = run
+        .entries
+        .iter()
+        .map(|e| format!("{} {} {} {}\n", run.timestamp, e.location, e.git_ref, e.sha))
+        .collect::<String>();
+    lines + "\n"
+}
+
+fn parse_runs(journal: &str) -> Vec<Run> {
+    journal
+        .split("\n\n")
+        .filter_map(|block| {
+            let mut timestamp = None;
+            let mut entries = vec![];
+            for line in block.lines() {
+                if let [time, location, git_ref, sha] = line.split(' ').collect::<Vec<&str>>()[..] {
+                    if let Ok(time) = time.parse::<u64>() {
+                        timestamp = Some(time);
+                        entries.push(Entry {
+                            location: location.to_owned(),
+                            git_ref: git_ref.to_owned(),
+                            sha: sha.to_owned(),
+                        });
+                    }
+                }
+            }
+            timestamp.map(|timestamp| Run { timestamp, entries })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use super::{format_run, parse_runs, Entry, Run};
+
+    fn run() -> Run {
+        Run {
+            timestamp: 1700000000,
+            entries: vec![
+                Entry::local("refs/heads/feature".to_owned(), "3a9ea97".to_owned()),
+                Entry::remote(
+                    "origin",
+                    "refs/heads/feature".to_owned(),
+                    "4b8fb08".to_owned(),
+                ),
+                Entry::local("refs/tags/ci-build-1".to_owned(), "5c7ac19".to_owned()),
+            ],
+        }
+    }
+
+    #[test]
+    fn test_runs_round_trip_through_the_journal() {
+        let mut second = run();
+        second.entries.truncate(1);
+        let journal = format_run(&run()) + &format_run(&second);
+
+        assert_eq!(
+            "1700000000 local refs/heads/feature 3a9ea97\n\
+             1700000000 origin refs/heads/feature 4b8fb08\n\
+             1700000000 local refs/tags/ci-build-1 5c7ac19\n\
+             \n\
+             1700000000 local refs/heads/feature 3a9ea97\n\
+             \n",
+            journal
+        );
+        assert_eq!(vec![run(), second], parse_runs(&journal));
+    }
+
+    #[test]
+    fn test_unreadable_lines_are_skipped() {
+        let journal = "1 local refs/heads/a 3a9ea97\n\
+                       not a journal line\n\
+                       \n\
+                       \n\
+                       2 origin refs/heads/b 4b8fb08\n";
+
+        let runs = parse_runs(journal);
+
+        assert_eq!(2, runs.len());
+        assert_eq!("a", runs[0].summary());
+        assert_eq!("origin/b", runs[1].summary());
+    }
+
+    #[test]
+    fn test_summary() {
+        assert_eq!("feature, origin/feature, tag ci-build-1", run().summary());
+    }
+}
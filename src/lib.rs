@@ -2,12 +2,22 @@
 
 extern crate clap;
 
+#[macro_use]
+extern crate crossterm;
+
 extern crate regex;
 
+extern crate serde;
+#[macro_use]
+extern crate serde_derive;
+extern crate serde_json;
+
 pub mod cli;
 
 use clap::ArgMatches;
 
+mod archive;
+
 mod branches;
 use branches::Branches;
 
@@ -17,33 +27,201 @@ pub use commands::validate_git_installation;
 mod error;
 use error::Error;
 
+mod journal;
+use journal::Run;
+
 mod options;
-use options::Options;
+use options::{CleanMode, Options};
+
+mod pattern;
+
+mod picker;
+
+mod plan;
+use plan::Plan;
+
+pub mod report;
+use report::Status;
 
-pub fn run(matches: &ArgMatches) -> Result<(), error::Error> {
+mod tags;
+use tags::Tags;
+
+pub fn run(matches: &ArgMatches) -> Result<Status, error::Error> {
     validate_git_installation()?;
 
-    let options = Options::new(matches);
+    if let Some(undo_matches) = matches.subcommand_matches("undo") {
+        return undo(undo_matches);
+    }
+
+    if let Some(apply_matches) = matches.subcommand_matches("apply") {
+        return apply(apply_matches);
+    }
+
+    if let Some(archived_matches) = matches.subcommand_matches("archived") {
+        return archived(archived_matches);
+    }
+
+    let options = Options::new(matches)?;
     options.validate()?;
 
-    let branches = Branches::merged(&options);
+    match options.clean_mode {
+        CleanMode::Branches => clean_branches(matches, &options),
+        CleanMode::Tags => clean_tags(matches, &options),
+    }
+}
+
+fn clean_branches(matches: &ArgMatches, options: &Options) -> Result<Status, error::Error> {
+    let mut branches = Branches::merged(options)?;
+
+    if !options.force {
+        let refusals = branches.refuse_unmerged(options)?;
+        if !refusals.is_empty() {
+            println!("{}\n", refusals);
+        }
+    }
 
-    if branches.string.is_empty() {
+    if let Some(ref plan) = options.plan {
+        return write_plan(plan, &branches.refs_to_delete(options)?);
+    }
+
+    if branches.is_empty() {
         println!("No branches to delete, you're clean!");
-        return Ok(());
+        return Ok(Status::NothingToClean);
+    }
+
+    if options.interactive {
+        branches.pick(options)?;
+    } else if options.step {
+        branches.step_through(options)?;
+    } else if !matches.is_present("yes") {
+        branches.print_warning_and_prompt(options)?;
+    }
+
+    if branches.is_empty() {
+        println!("No branches picked, nothing to delete.");
+        return Ok(Status::NothingToClean);
+    }
+
+    if let Some(ref archive) = options.archive {
+        println!("\n{}", branches.archive(options, archive)?);
+    }
+
+    if let Some(ref bundle) = options.bundle {
+        println!("\n{}", branches.bundle(options, bundle)?);
+    }
+
+    if options.delete_mode.deletes_local() {
+        let removed_worktrees = commands::remove_worktrees(&branches)?;
+        if !removed_worktrees.is_empty() {
+            println!("\n{}", removed_worktrees);
+        }
+    }
+
+    delete(&entries(branches.refs_to_delete(options)?))
+}
+
+fn clean_tags(matches: &ArgMatches, options: &Options) -> Result<Status, error::Error> {
+    let tags = Tags::cleanable(options)?;
+
+    if let Some(ref plan) = options.plan {
+        return write_plan(plan, &tags.refs_to_delete(options));
+    }
+
+    if tags.is_empty() {
+        println!("No tags to delete, you're clean!");
+        return Ok(Status::NothingToClean);
     }
 
     if !matches.is_present("yes") {
-        branches.print_warning_and_prompt(&options.delete_mode)?;
+        tags.print_warning_and_prompt(options)?;
+    }
+
+    delete(&entries(tags.refs_to_delete(options)))
+}
+
+// Deletes the refs, prints what happened to each of them and journals the ones that are gone.
+fn delete(refs: &[journal::Entry]) -> Result<Status, error::Error> {
+    let report = commands::delete_refs(refs)?;
+    println!("\n{}", report);
+    Run::new(report.deleted()).record()?;
+    Ok(report.status())
+}
+
+fn entries<R>(refs: Vec<(journal::Entry, &R)>) -> Vec<journal::Entry> {
+    refs.into_iter().map(|(entry, _)| entry).collect()
+}
+
+fn write_plan<R: branches::SelectionReason>(
+    path: &str,
+    refs: &[(journal::Entry, &R)],
+) -> Result<Status, error::Error> {
+    Plan::new(refs).write(path)?;
+    println!("Wrote a plan to delete {} refs to {}", refs.len(), path);
+    Ok(Status::Done)
+}
+
+// Deletes the refs in a plan that still point where they did when it was written.
+fn apply(matches: &ArgMatches) -> Result<Status, error::Error> {
+    let (matching, mismatches) = Plan::read(matches.value_of("plan").unwrap())?.verify()?;
+
+    for mismatch in &mismatches {
+        println!("{}", mismatch);
     }
 
-    let msg = branches.delete(&options);
-    println!("\n{}", msg);
+    if matching.is_empty() {
+        println!("Nothing left to delete from the plan.");
+        return Ok(Status::NothingToClean);
+    }
+
+    delete(&matching)
+}
 
-    Ok(())
+fn undo(matches: &ArgMatches) -> Result<Status, error::Error> {
+    if let Some(bundle) = matches.value_of("bundle") {
+        println!("{}", journal::bundled_run(bundle)?.undo()?);
+        return Ok(Status::Done);
+    }
+
+    let mut runs = journal::runs()?;
+
+    if matches.is_present("list") {
+        for (number, run) in runs.iter().enumerate().rev() {
+            println!("{}: {}", number + 1, run.summary());
+        }
+        return Ok(Status::Done);
+    }
+
+    let run = match matches.value_of("run") {
+        Some(number) => number
+            .parse::<usize>()
+            .ok()
+            .filter(|n| *n > 0)
+            .and_then(|n| runs.into_iter().nth(n - 1))
+            .ok_or_else(|| Error::UnknownRun(number.to_owned()))?,
+        None => runs.pop().ok_or(Error::NothingToUndo)?,
+    };
+
+    println!("{}", run.undo()?);
+    Ok(Status::Done)
+}
+
+fn archived(matches: &ArgMatches) -> Result<Status, error::Error> {
+    match matches.values_of("restore") {
+        Some(restore) => {
+            for branch in restore {
+                println!("{}", archive::restore(branch)?);
+            }
+        }
+        None => {
+            for branch in archive::archived_branches()? {
+                println!("{}", branch);
+            }
+        }
+    }
+    Ok(Status::Done)
 }
 
 pub fn print_and_exit(error: &Error) {
     println!("{}", error);
-    std::process::exit(1);
+    std::process::exit(error.exit_code());
 }
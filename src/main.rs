@@ -7,5 +7,8 @@ use git_clean::*;
 fn main() {
     let matches = cli::build_cli().get_matches();
 
-    run(&matches).unwrap_or_else(|e| print_and_exit(&e));
+    match run(&matches) {
+        Ok(status) => std::process::exit(status.exit_code()),
+        Err(e) => print_and_exit(&e),
+    }
 }
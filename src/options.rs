@@ -1,7 +1,7 @@
 use clap::ArgMatches;
-use commands::{output, run_command};
+use commands::{local_branches, output, remote_branches, run_command};
 use error::Error;
-use regex::Regex;
+use pattern::Pattern;
 
 const DEFAULT_REMOTE: &str = "origin";
 const DEFAULT_BRANCH: &str = "main";
@@ -26,72 +26,317 @@ impl DeleteMode {
         }
     }
 
-    pub fn warning_message(&self) -> String {
+    pub fn deletes_local(&self) -> bool {
+        match *self {
+            Local | Both => true,
+            Remote => false,
+        }
+    }
+
+    pub fn deletes_remote(&self) -> bool {
+        match *self {
+            Remote | Both => true,
+            Local => false,
+        }
+    }
+
+    pub fn warning_message(&self, clean_mode: &CleanMode) -> String {
         let source = match *self {
             Local => "locally:",
             Remote => "remotely:",
             Both => "locally and remotely:",
         };
-        format!("The following branches will be deleted {}", source)
+        format!(
+            "The following {} will be deleted {}",
+            clean_mode.noun(),
+            source
+        )
+    }
+}
+
+// Where branches are archived before they're deleted.
+#[derive(Debug, PartialEq)]
+pub enum ArchiveMode {
+    Refs,
+    Tags,
+}
+
+impl ArchiveMode {
+    pub fn new(opts: &ArgMatches) -> Option<ArchiveMode> {
+        match opts.value_of("archive") {
+            Some("refs") => Some(ArchiveMode::Refs),
+            Some("tags") => Some(ArchiveMode::Tags),
+            _ => None,
+        }
+    }
+
+    pub fn prefix(&self) -> &str {
+        match *self {
+            ArchiveMode::Refs => "refs/archive/",
+            ArchiveMode::Tags => "refs/tags/archive/",
+        }
+    }
+
+    pub fn git_ref(&self, branch: &str) -> String {
+        format!("{}{}", self.prefix(), branch)
     }
 }
 
+// Whether branches or tags get cleaned.
+#[derive(Debug)]
+pub enum CleanMode {
+    Branches,
+    Tags,
+}
+
+impl CleanMode {
+    pub fn new(opts: &ArgMatches) -> CleanMode {
+        if opts.is_present("tags") {
+            CleanMode::Tags
+        } else {
+            CleanMode::Branches
+        }
+    }
+
+    pub fn noun(&self) -> &str {
+        match *self {
+            CleanMode::Branches => "branches",
+            CleanMode::Tags => "tags",
+        }
+    }
+}
+
+// Parses ages like `90d`, `12w`, `6m` or `1y` into a number of days. A bare number is read as days.
+pub fn parse_age(age: &str) -> Option<u64> {
+    let age = age.trim();
+    let (number, days_per_unit) = match age.chars().last()? {
+        'd' => (&age[..age.len() - 1], 1),
+        'w' => (&age[..age.len() - 1], 7),
+        'm' => (&age[..age.len() - 1], 30),
+        'y' => (&age[..age.len() - 1], 365),
+        _ => (age, 1),
+    };
+
+    number.parse::<u64>().ok().map(|n| n * days_per_unit)
+}
+
+fn patterns(opts: &ArgMatches, name: &str) -> Vec<Pattern> {
+    opts.values_of(name)
+        .map(|p| p.map(Pattern::new).collect())
+        .unwrap_or_default()
+}
+
+// A base branch that merged branches are compared against, along with the ref that holds it.
+#[derive(Debug)]
+pub struct BaseBranch {
+    pub name: String,
+    pub git_ref: String,
+}
+
 pub struct Options {
     pub remote: String,
-    pub base_branch: String,
+    pub base_branches: Vec<Pattern>,
     pub squashes: bool,
+    pub rebases: bool,
+    pub gone: bool,
+    pub prune_worktrees: bool,
+    pub sweep_remotes: bool,
+    // Only remote branches whose own commits were all authored by this email get deleted.
+    pub author: Option<String>,
     pub delete_unpushed_branches: bool,
-    pub ignored_branches: Vec<String>,
+    // Delete branches even when that loses commits nothing else has.
+    pub force: bool,
+    // Pick the branches to delete from a full-screen list instead of prompting for all of them.
+    pub interactive: bool,
+    // Ask about each branch in turn instead of prompting for all of them.
+    pub step: bool,
+    pub ignored_branches: Vec<Pattern>,
+    // When set, only branches matching one of these are considered at all.
+    pub included_branches: Vec<Pattern>,
+    pub older_than: Option<u64>,
     pub delete_mode: DeleteMode,
+    pub clean_mode: CleanMode,
+    pub archive: Option<ArchiveMode>,
+    // Archive remote branches on the remote too, instead of only locally.
+    pub archive_remote: bool,
+    // Where the branches about to be deleted are bundled first.
+    pub bundle: Option<String>,
+    // Where the refs that would be deleted are written to, instead of deleting them.
+    pub plan: Option<String>,
+    // The tags that can be cleaned, like `ci-build-*`.
+    pub tag_patterns: Vec<Pattern>,
+    // Tags that are kept, and make any cleanable tag they contain safe to delete.
+    pub release_tags: Vec<Pattern>,
 }
 
 impl Options {
-    pub fn new(opts: &ArgMatches) -> Options {
-        let default_ignored = Vec::new();
-        let ignored = opts
-            .values_of("ignore")
-            .map(|i| i.map(|v| v.to_owned()).collect::<Vec<String>>())
-            .unwrap_or(default_ignored);
-        Options {
-            remote: opts.value_of("remote").unwrap_or(DEFAULT_REMOTE).into(),
-            base_branch: opts.value_of("branch").unwrap_or(DEFAULT_BRANCH).into(),
-            ignored_branches: ignored,
+    pub fn new(opts: &ArgMatches) -> Result<Options, Error> {
+        let remote: String = opts.value_of("remote").unwrap_or(DEFAULT_REMOTE).into();
+        let base_branches = match opts.values_of("branch") {
+            Some(b) => b.map(Pattern::new).collect::<Vec<Pattern>>(),
+            None => vec![Pattern::new(&default_branch(&remote)?)],
+        };
+        let author = if opts.is_present("mine") || opts.is_present("author") {
+            let email = match opts.value_of("author") {
+                Some(author) => author.to_owned(),
+                None => user_email()?,
+            };
+            Some(mailmapped_email(&email)?)
+        } else {
+            None
+        };
+        Ok(Options {
+            remote,
+            base_branches,
+            ignored_branches: patterns(opts, "ignore"),
+            included_branches: patterns(opts, "include"),
+            older_than: opts.value_of("older-than").and_then(parse_age),
             squashes: opts.is_present("squashes"),
+            rebases: opts.is_present("rebases"),
+            gone: opts.is_present("gone"),
+            prune_worktrees: opts.is_present("prune-worktrees"),
+            sweep_remotes: opts.is_present("sweep-remotes"),
+            author,
             delete_unpushed_branches: opts.is_present("delete-unpushed-branches"),
+            force: opts.is_present("force"),
+            interactive: opts.is_present("interactive"),
+            step: opts.is_present("step"),
             delete_mode: DeleteMode::new(opts),
+            clean_mode: CleanMode::new(opts),
+            archive: ArchiveMode::new(opts),
+            archive_remote: opts.is_present("archive-remote"),
+            bundle: opts.value_of("bundle").map(|b| b.to_owned()),
+            plan: opts.value_of("plan").map(|p| p.to_owned()),
+            tag_patterns: patterns(opts, "tags"),
+            release_tags: patterns(opts, "release-tags"),
+        })
+    }
+
+    pub fn warning_message(&self) -> String {
+        let warning = self.delete_mode.warning_message(&self.clean_mode);
+        match self.archive {
+            Some(ref archive) => warning.replacen(
+                "will be deleted",
+                &format!("will be archived to {} and deleted", archive.prefix()),
+                1,
+            ),
+            None => warning,
         }
     }
 
     pub fn validate(&self) -> Result<(), Error> {
-        self.validate_base_branch()?;
+        if let CleanMode::Branches = self.clean_mode {
+            self.validate_base_branch()?;
+            self.validate_author()?;
+        }
         self.validate_remote()?;
         Ok(())
     }
 
+    // Every branch matching one of the base branch patterns, with the ref merged branches are
+    // compared against. The local branch wins over the remote one, since it can hold merges that
+    // haven't been pushed yet.
+    pub fn base_branches(&self) -> Result<Vec<BaseBranch>, Error> {
+        let local_branches = local_branches()?;
+        let remote_branches = remote_branches(&self.remote)?;
+        let mut base_branches: Vec<BaseBranch> = vec![];
+
+        for pattern in &self.base_branches {
+            let mut names = if pattern.is_glob() {
+                local_branches
+                    .iter()
+                    .chain(remote_branches.iter())
+                    .filter(|b| pattern.is_match(b))
+                    .cloned()
+                    .collect::<Vec<String>>()
+            } else {
+                vec![pattern.as_str().to_owned()]
+            };
+            names.sort();
+            names.dedup();
+
+            for name in names {
+                let git_ref = if local_branches.contains(&name) {
+                    format!("refs/heads/{}", name)
+                } else if remote_branches.contains(&name) {
+                    format!("refs/remotes/{}/{}", self.remote, name)
+                } else {
+                    continue;
+                };
+
+                if !base_branches.iter().any(|b| b.name == name) {
+                    base_branches.push(BaseBranch { name, git_ref });
+                }
+            }
+        }
+
+        Ok(base_branches)
+    }
+
+    pub fn is_base_branch(&self, branch: &str) -> bool {
+        self.base_branches.iter().any(|b| b.is_match(branch))
+    }
+
+    pub fn is_ignored(&self, branch: &str) -> bool {
+        self.ignored_branches.iter().any(|b| b.is_match(branch))
+    }
+
+    pub fn is_included(&self, branch: &str) -> bool {
+        self.included_branches.is_empty()
+            || self.included_branches.iter().any(|b| b.is_match(branch))
+    }
+
+    pub fn is_release_tag(&self, tag: &str) -> bool {
+        self.release_tags.iter().any(|t| t.is_match(tag))
+    }
+
+    // Tags matching --tags can be deleted, unless they're release tags too.
+    pub fn is_cleanable_tag(&self, tag: &str) -> bool {
+        self.tag_patterns.iter().any(|t| t.is_match(tag)) && !self.is_release_tag(tag)
+    }
+
+    // Base branches, ignored branches and branches outside the included ones are never deleted,
+    // locally or on the remote.
+    pub fn is_protected(&self, branch: &str) -> bool {
+        self.is_base_branch(branch) || self.is_ignored(branch) || !self.is_included(branch)
+    }
+
     fn validate_base_branch(&self) -> Result<(), Error> {
-        let current_branch = output(&["git", "rev-parse", "--abbrev-ref", "HEAD"]);
+        let base_branches = self.base_branches()?;
 
-        if current_branch != self.base_branch {
-            return Err(Error::CurrentBranchInvalid);
-        };
+        // Globs are allowed to match nothing, as long as something is left to compare against.
+        for pattern in &self.base_branches {
+            if !pattern.is_glob() && !base_branches.iter().any(|b| b.name == pattern.as_str()) {
+                return Err(Error::InvalidBaseBranch(pattern.as_str().to_owned()));
+            }
+        }
+
+        if base_branches.is_empty() {
+            let patterns = self
+                .base_branches
+                .iter()
+                .map(|b| b.as_str())
+                .collect::<Vec<&str>>();
+            return Err(Error::InvalidBaseBranch(patterns.join(", ")));
+        }
+
+        Ok(())
+    }
+
+    fn validate_author(&self) -> Result<(), Error> {
+        if let Some(ref author) = self.author {
+            if author.is_empty() {
+                return Err(Error::MissingAuthor);
+            }
+        }
 
         Ok(())
     }
 
     fn validate_remote(&self) -> Result<(), Error> {
-        let remote_rx = Regex::new(&self.remote).unwrap();
-        let remotes = run_command(&["git", "remote"]);
-        let remotes_output = std::str::from_utf8(&remotes.stdout).unwrap();
-
-        let remote_result =
-            remote_rx
-                .captures_iter(remotes_output)
-                .fold(String::new(), |mut acc, e| {
-                    acc.push_str(&e[0]);
-                    acc
-                });
-
-        if remote_result.is_empty() {
+        let remotes = output(&["git", "remote"])?;
+
+        if !remotes.lines().any(|remote| remote == self.remote) {
             return Err(Error::InvalidRemote);
         }
 
@@ -99,17 +344,74 @@ impl Options {
     }
 }
 
+// The email `.mailmap` maps `email` to, which is also what `%aE` shows for commits.
+fn mailmapped_email(email: &str) -> Result<String, Error> {
+    if email.is_empty() {
+        return Ok(String::new());
+    }
+
+    let mapped = output(&["git", "check-mailmap", &format!("<{}>", email)])?;
+    match (mapped.rfind('<'), mapped.rfind('>')) {
+        (Some(start), Some(end)) if start < end => Ok(mapped[start + 1..end].to_owned()),
+        _ => Ok(email.to_owned()),
+    }
+}
+
+// `user.email`, or nothing when it isn't set, which makes `git config` fail without a message.
+fn user_email() -> Result<String, Error> {
+    let email_cmd = run_command(&["git", "config", "user.email"])?;
+    Ok(String::from_utf8_lossy(&email_cmd.stdout).trim().to_owned())
+}
+
+// The remote's default branch, read from `refs/remotes/<remote>/HEAD` when it's been set up
+// locally, and asked from the remote itself otherwise.
+// Either can be missing, or the remote unreachable, in which case this falls back to `main`.
+fn default_branch(remote: &str) -> Result<String, Error> {
+    let remote_prefix = format!("{}/", remote);
+    let remote_head = run_command(&[
+        "git",
+        "symbolic-ref",
+        "--quiet",
+        "--short",
+        &format!("refs/remotes/{}/HEAD", remote),
+    ])?;
+    let remote_head = String::from_utf8_lossy(&remote_head.stdout);
+    if remote_head.starts_with(&remote_prefix) {
+        return Ok(remote_head
+            .trim()
+            .trim_start_matches(&remote_prefix)
+            .to_owned());
+    }
+
+    // Prints `ref: refs/heads/<branch>\tHEAD` when the remote's HEAD points at a branch.
+    let symref = run_command(&["git", "ls-remote", "--symref", remote, "HEAD"])?;
+    let symref = String::from_utf8_lossy(&symref.stdout);
+    let symref_branch = symref.lines().find_map(|line| {
+        line.strip_prefix("ref: refs/heads/")
+            .and_then(|rest| rest.strip_suffix("\tHEAD"))
+    });
+    if let Some(branch) = symref_branch {
+        return Ok(branch.to_owned());
+    }
+
+    Ok(DEFAULT_BRANCH.to_owned())
+}
+
 #[cfg(test)]
 mod test {
-    use super::{DeleteMode, Options};
+    use super::{parse_age, ArchiveMode, CleanMode, DeleteMode, Options};
     use clap;
     use cli;
 
     // Helpers
-    fn parse_args(args: Vec<&str>) -> clap::ArgMatches {
+    fn parse_args(args: Vec<&str>) -> clap::ArgMatches<'_> {
         cli::build_cli().get_matches_from(args)
     }
 
+    fn base_branches(options: &Options) -> Vec<&str> {
+        options.base_branches.iter().map(|b| b.as_str()).collect()
+    }
+
     // DeleteMode tests
     #[test]
     fn test_delete_mode_new() {
@@ -117,21 +419,21 @@ mod test {
 
         match DeleteMode::new(&matches) {
             DeleteMode::Local => (),
-            other @ _ => panic!("Expected a DeleteMode::Local, but found: {:?}", other),
+            other => panic!("Expected a DeleteMode::Local, but found: {:?}", other),
         };
 
         let matches = parse_args(vec!["git-clean", "-r"]);
 
         match DeleteMode::new(&matches) {
             DeleteMode::Remote => (),
-            other @ _ => panic!("Expected a DeleteMode::Remote, but found: {:?}", other),
+            other => panic!("Expected a DeleteMode::Remote, but found: {:?}", other),
         };
 
         let matches = parse_args(vec!["git-clean"]);
 
         match DeleteMode::new(&matches) {
             DeleteMode::Both => (),
-            other @ _ => panic!("Expected a DeleteMode::Both, but found: {:?}", other),
+            other => panic!("Expected a DeleteMode::Both, but found: {:?}", other),
         };
     }
 
@@ -139,55 +441,166 @@ mod test {
     fn test_delete_mode_warning_message() {
         assert_eq!(
             "The following branches will be deleted locally:",
-            DeleteMode::Local.warning_message()
+            DeleteMode::Local.warning_message(&CleanMode::Branches)
         );
         assert_eq!(
             "The following branches will be deleted remotely:",
-            DeleteMode::Remote.warning_message()
+            DeleteMode::Remote.warning_message(&CleanMode::Branches)
         );
         assert_eq!(
             "The following branches will be deleted locally and remotely:",
-            DeleteMode::Both.warning_message()
+            DeleteMode::Both.warning_message(&CleanMode::Branches)
+        );
+        assert_eq!(
+            "The following tags will be deleted locally and remotely:",
+            DeleteMode::Both.warning_message(&CleanMode::Tags)
         );
     }
 
-    // Options tests
+    // CleanMode tests
     #[test]
-    fn test_git_options_new() {
+    fn test_clean_mode_new() {
+        let matches = parse_args(vec![
+            "git-clean",
+            "--tags",
+            "ci-build-*",
+            "--older-than",
+            "7d",
+        ]);
+
+        match CleanMode::new(&matches) {
+            CleanMode::Tags => (),
+            other => panic!("Expected a CleanMode::Tags, but found: {:?}", other),
+        };
+
         let matches = parse_args(vec!["git-clean"]);
-        let git_options = Options::new(&matches);
 
-        assert_eq!("main".to_owned(), git_options.base_branch);
-        assert_eq!("origin".to_owned(), git_options.remote);
+        match CleanMode::new(&matches) {
+            CleanMode::Branches => (),
+            other => panic!("Expected a CleanMode::Branches, but found: {:?}", other),
+        };
+    }
+
+    // ArchiveMode tests
+    #[test]
+    fn test_archive_mode_new() {
+        let matches = parse_args(vec!["git-clean", "--archive", "refs"]);
+
+        assert_eq!(Some(ArchiveMode::Refs), ArchiveMode::new(&matches));
+        assert_eq!(
+            "refs/archive/feature/x",
+            ArchiveMode::Refs.git_ref("feature/x")
+        );
+
+        let matches = parse_args(vec![
+            "git-clean",
+            "-b",
+            "main",
+            "--archive",
+            "tags",
+            "--archive-remote",
+        ]);
+        let git_options = Options::new(&matches).unwrap();
 
+        assert_eq!(Some(ArchiveMode::Tags), git_options.archive);
+        assert!(git_options.archive_remote);
+        assert_eq!(
+            "The following branches will be archived to refs/tags/archive/ and deleted locally and remotely:",
+            git_options.warning_message()
+        );
+
+        let matches = parse_args(vec!["git-clean"]);
+
+        assert_eq!(None, ArchiveMode::new(&matches));
+    }
+
+    // Age tests
+    #[test]
+    fn test_parse_age() {
+        assert_eq!(Some(90), parse_age("90"));
+        assert_eq!(Some(90), parse_age("90d"));
+        assert_eq!(Some(14), parse_age("2w"));
+        assert_eq!(Some(180), parse_age("6m"));
+        assert_eq!(Some(365), parse_age("1y"));
+        assert_eq!(None, parse_age(""));
+        assert_eq!(None, parse_age("d"));
+        assert_eq!(None, parse_age("ninety days"));
+    }
+
+    // Options tests
+    #[test]
+    fn test_git_options_new() {
+        // Every case passes -b, so the default branch isn't looked up in whatever repo the tests
+        // run in. tests/remote.rs covers that.
         let matches = parse_args(vec!["git-clean", "-b", "stable"]);
-        let git_options = Options::new(&matches);
+        let git_options = Options::new(&matches).unwrap();
 
-        assert_eq!("stable".to_owned(), git_options.base_branch);
+        assert_eq!(vec!["stable"], base_branches(&git_options));
         assert_eq!("origin".to_owned(), git_options.remote);
 
-        let matches = parse_args(vec!["git-clean", "-R", "upstream"]);
-        let git_options = Options::new(&matches);
+        let matches = parse_args(vec!["git-clean", "-b", "main", "-b", "release/*"]);
+        let git_options = Options::new(&matches).unwrap();
+
+        assert_eq!(vec!["main", "release/*"], base_branches(&git_options));
+        assert!(git_options.is_base_branch("release/1.0"));
+        assert!(!git_options.is_base_branch("feature/1.0"));
 
-        assert_eq!("main".to_owned(), git_options.base_branch);
+        let matches = parse_args(vec!["git-clean", "-b", "main", "-R", "upstream"]);
+        let git_options = Options::new(&matches).unwrap();
+
+        assert_eq!(vec!["main"], base_branches(&git_options));
         assert_eq!("upstream".to_owned(), git_options.remote);
+        assert_eq!(None, git_options.older_than);
         assert!(!git_options.squashes);
+        assert!(!git_options.rebases);
+        assert!(!git_options.gone);
+        assert!(!git_options.prune_worktrees);
+        assert!(!git_options.sweep_remotes);
+        assert_eq!(None, git_options.author);
         assert!(!git_options.delete_unpushed_branches);
 
         let matches = parse_args(vec![
             "git-clean",
+            "-b",
+            "main",
             "-R",
             "upstream",
             "--squashes",
+            "--rebases",
+            "--gone",
+            "--prune-worktrees",
+            "--sweep-remotes",
             "--delete-unpushed-branches",
         ]);
-        let git_options = Options::new(&matches);
+        let git_options = Options::new(&matches).unwrap();
 
         assert!(git_options.squashes);
+        assert!(git_options.rebases);
+        assert!(git_options.gone);
+        assert!(git_options.prune_worktrees);
+        assert!(git_options.sweep_remotes);
         assert!(git_options.delete_unpushed_branches);
 
         let matches = parse_args(vec![
             "git-clean",
+            "-b",
+            "main",
+            "--author",
+            "someone@example.com",
+        ]);
+        let git_options = Options::new(&matches).unwrap();
+
+        assert_eq!(Some("someone@example.com".to_owned()), git_options.author);
+
+        let matches = parse_args(vec!["git-clean", "-b", "main", "--older-than", "3w"]);
+        let git_options = Options::new(&matches).unwrap();
+
+        assert_eq!(Some(21), git_options.older_than);
+
+        let matches = parse_args(vec![
+            "git-clean",
+            "-b",
+            "main",
             "-i",
             "branch1",
             "-i",
@@ -195,11 +608,55 @@ mod test {
             "-i",
             "branch3",
         ]);
-        let git_options = Options::new(&matches);
+        let git_options = Options::new(&matches).unwrap();
 
         assert_eq!(
-            git_options.ignored_branches,
+            git_options
+                .ignored_branches
+                .iter()
+                .map(|b| b.as_str())
+                .collect::<Vec<&str>>(),
             vec!["branch1", "branch2", "branch3"]
         );
+
+        let matches = parse_args(vec![
+            "git-clean",
+            "-b",
+            "main",
+            "-i",
+            "release/*",
+            "-i",
+            "hotfix/**",
+            "-i",
+            "^keep-.*$",
+        ]);
+        let git_options = Options::new(&matches).unwrap();
+
+        assert!(git_options.is_ignored("release/1.0"));
+        assert!(git_options.is_ignored("hotfix/a/b"));
+        assert!(git_options.is_ignored("keep-me"));
+        assert!(!git_options.is_ignored("feature/keep-me"));
+        assert!(git_options.is_protected("main"));
+        assert!(!git_options.is_protected("feature/keep-me"));
+
+        let matches = parse_args(vec![
+            "git-clean",
+            "-b",
+            "main",
+            "--include",
+            "feature/*",
+            "--include",
+            "dependabot/**",
+            "-i",
+            "feature/keep",
+        ]);
+        let git_options = Options::new(&matches).unwrap();
+
+        assert!(git_options.is_included("feature/login"));
+        assert!(git_options.is_included("dependabot/cargo/regex-1.7"));
+        assert!(!git_options.is_included("fix-login"));
+        assert!(!git_options.is_protected("feature/login"));
+        assert!(git_options.is_protected("fix-login"));
+        assert!(git_options.is_protected("feature/keep"));
     }
 }
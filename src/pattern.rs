@@ -0,0 +1,131 @@
+use regex::Regex;
+
+// A branch name pattern, like `release/*`. `*` and `?` match within a single path component,
+// while `**` matches across them, so `hotfix/**` covers `hotfix/a/b` too. Patterns that start with
+// `^` or end with `$`, like `^keep-.*$`, are regular expressions instead.
+#[derive(Debug)]
+pub struct Pattern {
+    source: String,
+    regex: Regex,
+}
+
+impl Pattern {
+    // Panics on invalid regular expressions, so user input should go through `validate` first.
+    pub fn new(source: &str) -> Pattern {
+        Pattern {
+            source: source.to_owned(),
+            regex: Regex::new(&to_regex(source)).unwrap(),
+        }
+    }
+
+    pub fn validate(source: &str) -> Result<(), String> {
+        Regex::new(&to_regex(source))
+            .map(|_| ())
+            .map_err(|e| format!("'{}' isn't a valid pattern: {}", source, e))
+    }
+
+    pub fn is_glob(&self) -> bool {
+        is_regex(&self.source) || self.source.contains(['*', '?'])
+    }
+
+    pub fn is_match(&self, branch: &str) -> bool {
+        self.regex.is_match(branch)
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.source
+    }
+}
+
+fn is_regex(source: &str) -> bool {
+    source.starts_with('^') || source.ends_with('$')
+}
+
+fn to_regex(source: &str) -> String {
+    if is_regex(source) {
+        source.to_owned()
+    } else {
+        glob_to_regex(source)
+    }
+}
+
+fn glob_to_regex(glob: &str) -> String {
+    let mut regex = String::from("^");
+    let mut chars = glob.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        match c {
+            '*' if chars.peek() == Some(&'*') => {
+                chars.next();
+                regex.push_str(".*");
+            }
+            '*' => regex.push_str("[^/]*"),
+            '?' => regex.push_str("[^/]"),
+            c => regex.push_str(&regex::escape(&c.to_string())),
+        }
+    }
+
+    regex.push('$');
+    regex
+}
+
+#[cfg(test)]
+mod test {
+    use super::Pattern;
+
+    #[test]
+    fn test_literal_pattern() {
+        let pattern = Pattern::new("main");
+
+        assert!(!pattern.is_glob());
+        assert!(pattern.is_match("main"));
+        assert!(!pattern.is_match("main2"));
+        assert!(!pattern.is_match("old-main"));
+    }
+
+    #[test]
+    fn test_single_star_stays_in_one_component() {
+        let pattern = Pattern::new("release/*");
+
+        assert!(pattern.is_glob());
+        assert!(pattern.is_match("release/1.0"));
+        assert!(!pattern.is_match("release/1.0/hotfix"));
+        assert!(!pattern.is_match("release"));
+    }
+
+    #[test]
+    fn test_double_star_crosses_components() {
+        let pattern = Pattern::new("hotfix/**");
+
+        assert!(pattern.is_match("hotfix/a"));
+        assert!(pattern.is_match("hotfix/a/b"));
+        assert!(!pattern.is_match("feature/hotfix/a"));
+    }
+
+    #[test]
+    fn test_regex_pattern() {
+        let pattern = Pattern::new("^keep-.*$");
+
+        assert!(pattern.is_glob());
+        assert!(pattern.is_match("keep-this"));
+        assert!(!pattern.is_match("dont-keep-this"));
+
+        let unanchored_end = Pattern::new("^dependabot/");
+        assert!(unanchored_end.is_match("dependabot/cargo/regex-1.7"));
+    }
+
+    #[test]
+    fn test_validate() {
+        assert!(Pattern::validate("release/*").is_ok());
+        assert!(Pattern::validate("^keep-.*$").is_ok());
+        assert!(Pattern::validate("^keep-(.*$").is_err());
+    }
+
+    #[test]
+    fn test_special_characters_are_escaped() {
+        let pattern = Pattern::new("v1.0+?");
+
+        assert!(pattern.is_match("v1.0+a"));
+        assert!(!pattern.is_match("v100+a"));
+    }
+}
@@ -0,0 +1,394 @@
+use commands::output;
+use crossterm::cursor::{Hide, MoveTo, Show};
+use crossterm::event::{read, Event, KeyCode, KeyEvent, KeyEventKind, KeyModifiers};
+use crossterm::style::{Attribute, Print, SetAttribute};
+use crossterm::terminal::{
+    disable_raw_mode, enable_raw_mode, size, Clear, ClearType, EnterAlternateScreen,
+    LeaveAlternateScreen,
+};
+use error::Error;
+use std::collections::btree_map::Entry::Vacant;
+use std::collections::BTreeMap;
+use std::io::{stdin, stdout, IsTerminal, Stdout, Write};
+
+const HELP: &str = "space: toggle  tab: toggle group  ctrl-a: toggle all  enter: delete  esc: quit";
+const PREVIEW_COMMITS: &str = "5";
+
+// Something that can be picked for deletion, under the heading of the group it belongs to.
+#[derive(Debug)]
+pub struct Item {
+    pub label: String,
+    pub heading: String,
+    pub reason: String,
+    // What the preview is shown for.
+    pub git_ref: String,
+}
+
+#[derive(Debug, PartialEq)]
+enum Done {
+    Picked(Vec<bool>),
+    Aborted,
+}
+
+// Which items are picked, what they're filtered by and where the cursor is, apart from the
+// terminal so it can be tested.
+struct Picker {
+    items: Vec<Item>,
+    picked: Vec<bool>,
+    filter: String,
+    // An index into the items the filter shows.
+    cursor: usize,
+    confirming: bool,
+}
+
+impl Picker {
+    // Every item starts out picked, like the `Continue?` prompt would delete all of them.
+    fn new(items: Vec<Item>) -> Picker {
+        let picked = vec![true; items.len()];
+        Picker {
+            items,
+            picked,
+            filter: String::new(),
+            cursor: 0,
+            confirming: false,
+        }
+    }
+
+    fn shown(&self) -> Vec<usize> {
+        let filter = self.filter.to_lowercase();
+        (0..self.items.len())
+            .filter(|&i| self.items[i].label.to_lowercase().contains(&filter))
+            .collect()
+    }
+
+    fn highlighted(&self) -> Option<usize> {
+        self.shown().get(self.cursor).cloned()
+    }
+
+    fn picked_count(&self) -> usize {
+        self.picked.iter().filter(|&&p| p).count()
+    }
+
+    fn move_cursor(&mut self, up: bool) {
+        let shown = self.shown().len();
+        if up {
+            self.cursor = self.cursor.saturating_sub(1);
+        } else if self.cursor + 1 < shown {
+            self.cursor += 1;
+        }
+    }
+
+    // Picks all of `items` unless they all are already, in which case it unpicks them.
+    fn toggle_all(&mut self, items: &[usize]) {
+        let pick = !items.iter().all(|&i| self.picked[i]);
+        for &i in items {
+            self.picked[i] = pick;
+        }
+    }
+
+    fn toggle_group(&mut self) {
+        if let Some(highlighted) = self.highlighted() {
+            let heading = &self.items[highlighted].heading;
+            let group = self
+                .shown()
+                .into_iter()
+                .filter(|&i| self.items[i].heading == *heading)
+                .collect::<Vec<usize>>();
+            self.toggle_all(&group);
+        }
+    }
+
+    fn set_filter(&mut self, filter: String) {
+        self.filter = filter;
+        self.cursor = 0;
+    }
+
+    fn handle(&mut self, key: KeyEvent) -> Option<Done> {
+        let control = key.modifiers.contains(KeyModifiers::CONTROL);
+
+        if self.confirming {
+            match key.code {
+                KeyCode::Char('y') | KeyCode::Enter => {
+                    return Some(Done::Picked(self.picked.clone()))
+                }
+                KeyCode::Char('c') if control => return Some(Done::Aborted),
+                _ => self.confirming = false,
+            }
+            return None;
+        }
+
+        match key.code {
+            KeyCode::Char('c') if control => return Some(Done::Aborted),
+            KeyCode::Char('a') if control => {
+                let shown = self.shown();
+                self.toggle_all(&shown);
+            }
+            KeyCode::Char(' ') => {
+                if let Some(highlighted) = self.highlighted() {
+                    self.picked[highlighted] = !self.picked[highlighted];
+                }
+            }
+            KeyCode::Char(c) => {
+                let filter = format!("{}{}", self.filter, c);
+                self.set_filter(filter);
+            }
+            KeyCode::Backspace => {
+                let mut filter = self.filter.clone();
+                filter.pop();
+                self.set_filter(filter);
+            }
+            KeyCode::Esc if !self.filter.is_empty() => self.set_filter(String::new()),
+            KeyCode::Esc => return Some(Done::Aborted),
+            KeyCode::Up => self.move_cursor(true),
+            KeyCode::Down => self.move_cursor(false),
+            KeyCode::Tab => self.toggle_group(),
+            KeyCode::Enter => self.confirming = true,
+            _ => (),
+        }
+        None
+    }
+
+    // The shown items under their group's heading, along with the line the cursor is on.
+    fn list(&self) -> (Vec<String>, usize) {
+        let mut lines = vec![];
+        let mut cursor_line = 0;
+        let mut heading = None;
+
+        for (position, i) in self.shown().into_iter().enumerate() {
+            let item = &self.items[i];
+            if heading != Some(&item.heading) {
+                heading = Some(&item.heading);
+                lines.push(item.heading.clone());
+            }
+            if position == self.cursor {
+                cursor_line = lines.len();
+            }
+            lines.push(format!(
+                "{} [{}] {}",
+                if position == self.cursor { ">" } else { " " },
+                if self.picked[i] { "x" } else { " " },
+                item.label
+            ));
+        }
+        (lines, cursor_line)
+    }
+}
+
+// Shows the items in a full-screen list with all of them picked, and returns whether each one is
+// still picked once the user confirms.
+pub fn pick(title: &str, items: Vec<Item>) -> Result<Vec<bool>, Error> {
+    if !stdin().is_terminal() || !stdout().is_terminal() {
+        return Err(Error::NotATerminal);
+    }
+
+    let mut picker = Picker::new(items);
+    let mut previews = BTreeMap::new();
+    let screen = Screen::enter()?;
+
+    loop {
+        let preview = match picker.highlighted() {
+            Some(highlighted) => {
+                if let Vacant(entry) = previews.entry(highlighted) {
+                    entry.insert(preview(&picker.items[highlighted])?);
+                }
+                previews[&highlighted].clone()
+            }
+            None => vec![],
+        };
+        screen.draw(title, &picker, &preview)?;
+
+        if let Event::Key(key) = read()? {
+            if key.kind == KeyEventKind::Release {
+                continue;
+            }
+            match picker.handle(key) {
+                Some(Done::Picked(picked)) => return Ok(picked),
+                Some(Done::Aborted) => return Err(Error::ExitEarly),
+                None => (),
+            }
+        }
+    }
+}
+
+// Why the highlighted item was picked, who last committed to it, when, and its latest commits.
+fn preview(item: &Item) -> Result<Vec<String>, Error> {
+    let last_commit = output(&["git", "log", "-1", "--format=%an <%ae>%n%cr", &item.git_ref])?;
+    let mut last_commit = last_commit.lines();
+    let commits = output(&[
+        "git",
+        "log",
+        "-n",
+        PREVIEW_COMMITS,
+        "--format=%h %s",
+        &item.git_ref,
+    ])?;
+
+    let mut lines = vec![
+        item.label.clone(),
+        format!("Why:    {}", item.reason),
+        format!("Author: {}", last_commit.next().unwrap_or("")),
+        format!("Age:    {}", last_commit.next().unwrap_or("")),
+        String::new(),
+    ];
+    lines.extend(commits.lines().map(|c| format!("  {}", c)));
+    Ok(lines)
+}
+
+// The alternate screen in raw mode, which is left again however picking ends.
+struct Screen {
+    stdout: Stdout,
+}
+
+impl Screen {
+    fn enter() -> Result<Screen, Error> {
+        enable_raw_mode()?;
+        let mut stdout = stdout();
+        execute!(stdout, EnterAlternateScreen, Hide)?;
+        Ok(Screen { stdout })
+    }
+
+    fn draw(&self, title: &str, picker: &Picker, preview: &[String]) -> Result<(), Error> {
+        let (width, height) = size()?;
+        let (width, height) = (width as usize, height as usize);
+        let (list, cursor_line) = picker.list();
+
+        let status = if picker.confirming {
+            format!(
+                "Delete {} of {} branches? (Y/n)",
+                picker.picked_count(),
+                picker.items.len()
+            )
+        } else {
+            format!("Filter: {}", picker.filter)
+        };
+        let mut lines = vec![title.to_owned(), HELP.to_owned(), status, String::new()];
+
+        // The list gets whatever the preview doesn't need, scrolled to keep the cursor on screen.
+        let list_height = height
+            .saturating_sub(lines.len() + preview.len() + 1)
+            .max(1);
+        let scroll = (cursor_line + 1).saturating_sub(list_height);
+        lines.extend(list.into_iter().skip(scroll).take(list_height));
+        while lines.len() < height.saturating_sub(preview.len() + 1) {
+            lines.push(String::new());
+        }
+        lines.push("-".repeat(width));
+        lines.extend(preview.iter().cloned());
+
+        let mut out = &self.stdout;
+        queue!(out, Clear(ClearType::All))?;
+        for (row, line) in lines.iter().take(height).enumerate() {
+            let line = line.chars().take(width).collect::<String>();
+            queue!(out, MoveTo(0, row as u16))?;
+            if row == 0 || (picker.confirming && row == 2) {
+                queue!(
+                    out,
+                    SetAttribute(Attribute::Bold),
+                    Print(line),
+                    SetAttribute(Attribute::Reset)
+                )?;
+            } else {
+                queue!(out, Print(line))?;
+            }
+        }
+        out.flush()?;
+        Ok(())
+    }
+}
+
+impl Drop for Screen {
+    fn drop(&mut self) {
+        let _ = execute!(self.stdout, Show, LeaveAlternateScreen);
+        let _ = disable_raw_mode();
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{Done, Item, Picker};
+    use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+
+    fn item(label: &str, heading: &str) -> Item {
+        Item {
+            label: label.to_owned(),
+            heading: heading.to_owned(),
+            reason: String::new(),
+            git_ref: format!("refs/heads/{}", label),
+        }
+    }
+
+    fn picker() -> Picker {
+        Picker::new(vec![
+            item("fix-login", "Merged into main:"),
+            item("fix-typo", "Merged into main:"),
+            item("spike", "Not on the remote, may hold unmerged work:"),
+        ])
+    }
+
+    fn press(picker: &mut Picker, keys: &[KeyCode]) -> Option<Done> {
+        let mut done = None;
+        for &key in keys {
+            done = picker.handle(KeyEvent::from(key));
+        }
+        done
+    }
+
+    #[test]
+    fn test_picker_toggles_items_and_groups() {
+        let mut picker = picker();
+
+        press(
+            &mut picker,
+            &[KeyCode::Down, KeyCode::Down, KeyCode::Char(' ')],
+        );
+        assert_eq!(vec![true, true, false], picker.picked);
+
+        press(&mut picker, &[KeyCode::Up, KeyCode::Tab]);
+        assert_eq!(vec![false, false, false], picker.picked);
+
+        press(&mut picker, &[KeyCode::Tab]);
+        assert_eq!(vec![true, true, false], picker.picked);
+    }
+
+    #[test]
+    fn test_picker_filters_by_typing() {
+        let mut picker = picker();
+
+        press(&mut picker, &[KeyCode::Char('T'), KeyCode::Char('y')]);
+        assert_eq!(vec![1], picker.shown());
+        assert_eq!(
+            (
+                vec!["Merged into main:".to_owned(), "> [x] fix-typo".to_owned()],
+                1
+            ),
+            picker.list()
+        );
+
+        picker.handle(KeyEvent::new(KeyCode::Char('a'), KeyModifiers::CONTROL));
+        assert_eq!(vec![true, false, true], picker.picked);
+
+        press(&mut picker, &[KeyCode::Esc]);
+        assert_eq!(vec![0, 1, 2], picker.shown());
+    }
+
+    #[test]
+    fn test_picker_confirms_before_finishing() {
+        let mut picker = picker();
+
+        assert_eq!(
+            None,
+            press(&mut picker, &[KeyCode::Char(' '), KeyCode::Enter])
+        );
+        assert!(picker.confirming);
+        assert_eq!(None, press(&mut picker, &[KeyCode::Char('n')]));
+        assert!(!picker.confirming);
+        assert_eq!(
+            Some(Done::Picked(vec![false, true, true])),
+            press(&mut picker, &[KeyCode::Enter, KeyCode::Char('y')])
+        );
+        assert_eq!(
+            Some(Done::Aborted),
+            press(&mut self::picker(), &[KeyCode::Esc])
+        );
+    }
+}
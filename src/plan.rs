@@ -0,0 +1,150 @@
+use branches::SelectionReason;
+use commands::*;
+use error::Error;
+use journal::Entry;
+use serde_json;
+use std::collections::BTreeMap;
+use std::fs;
+
+// A cleanup written by --plan, to be reviewed and then run with `git-clean apply`.
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+pub struct Plan {
+    pub refs: Vec<PlannedRef>,
+}
+
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+pub struct PlannedRef {
+    // `local`, or the remote the ref gets deleted on.
+    pub location: String,
+    #[serde(rename = "ref")]
+    pub git_ref: String,
+    // What the ref has to still point to when the plan is applied for it to be deleted.
+    pub sha: String,
+    pub reason: String,
+}
+
+impl Plan {
+    pub fn new<R: SelectionReason>(refs: &[(Entry, &R)]) -> Plan {
+        Plan {
+            refs: refs
+                .iter()
+                .map(|&(ref entry, reason)| PlannedRef {
+                    location: entry.location.clone(),
+                    git_ref: entry.git_ref.clone(),
+                    sha: entry.sha.clone(),
+                    reason: reason.description(),
+                })
+                .collect(),
+        }
+    }
+
+    pub fn read(path: &str) -> Result<Plan, Error> {
+        let plan = fs::read_to_string(path)?;
+        serde_json::from_str(&plan).map_err(|e| Error::InvalidPlan(path.to_owned(), e.to_string()))
+    }
+
+    pub fn write(&self, path: &str) -> Result<(), Error> {
+        let plan = serde_json::to_string_pretty(self)
+            .map_err(|e| Error::InvalidPlan(path.to_owned(), e.to_string()))?;
+        fs::write(path, plan + "\n")?;
+        Ok(())
+    }
+
+    // Splits the plan into the refs that still point where they did when it was made, and a
+    // message for each of the others, which must not be deleted.
+    pub fn verify(self) -> Result<(Vec<Entry>, Vec<String>), Error> {
+        let mut current_refs: BTreeMap<String, BTreeMap<String, String>> = BTreeMap::new();
+        let mut matching = vec![];
+        let mut mismatches = vec![];
+
+        for planned in self.refs {
+            let entry = Entry {
+                location: planned.location,
+                git_ref: planned.git_ref,
+                sha: planned.sha,
+            };
+            if !current_refs.contains_key(&entry.location) {
+                let refs = if entry.is_local() {
+                    local_refs()?
+                } else {
+                    remote_refs(&entry.location, "refs")?
+                };
+                current_refs.insert(entry.location.clone(), refs);
+            }
+            let current = current_refs[&entry.location].get(&entry.git_ref);
+
+            match current {
+                Some(sha) if *sha == entry.sha => matching.push(entry),
+                Some(sha) => mismatches.push(format!(
+                    "Skipped {}, it moved from {} to {}.",
+                    entry.label(),
+                    short_sha(&entry.sha),
+                    short_sha(sha)
+                )),
+                None => mismatches.push(format!("Skipped {}, it's already gone.", entry.label())),
+            }
+        }
+
+        Ok((matching, mismatches))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{Plan, PlannedRef};
+    use branches::Reason;
+    use journal::Entry;
+    use serde_json;
+
+    #[test]
+    fn test_plan_new() {
+        let merged = Reason::Merged("main".to_owned());
+        let refs = vec![
+            (
+                Entry::local("refs/heads/feature".to_owned(), "3a9ea97".to_owned()),
+                &merged,
+            ),
+            (
+                Entry::remote(
+                    "origin",
+                    "refs/heads/feature".to_owned(),
+                    "4b8fb08".to_owned(),
+                ),
+                &merged,
+            ),
+        ];
+
+        let plan = Plan::new(&refs);
+
+        assert_eq!(
+            vec![
+                PlannedRef {
+                    location: "local".to_owned(),
+                    git_ref: "refs/heads/feature".to_owned(),
+                    sha: "3a9ea97".to_owned(),
+                    reason: "merged into main".to_owned(),
+                },
+                PlannedRef {
+                    location: "origin".to_owned(),
+                    git_ref: "refs/heads/feature".to_owned(),
+                    sha: "4b8fb08".to_owned(),
+                    reason: "merged into main".to_owned(),
+                },
+            ],
+            plan.refs
+        );
+    }
+
+    #[test]
+    fn test_plan_json() {
+        let json = r#"{"refs": [{"location": "local", "ref": "refs/tags/rc-1", "sha": "3a9ea97", "reason": "already in release v1.0"}]}"#;
+
+        let plan: Plan = serde_json::from_str(json).unwrap();
+
+        assert_eq!("refs/tags/rc-1", plan.refs[0].git_ref);
+        assert_eq!(
+            plan,
+            serde_json::from_str(&serde_json::to_string(&plan).unwrap()).unwrap()
+        );
+    }
+}
@@ -0,0 +1,198 @@
+use commands::short_sha;
+use journal::Entry;
+use std::fmt::{Display, Error as FmtError, Formatter};
+
+pub const EXIT_FATAL: i32 = 1;
+pub const EXIT_NOTHING_TO_CLEAN: i32 = 2;
+pub const EXIT_ABORTED: i32 = 3;
+pub const EXIT_PARTIAL_FAILURE: i32 = 4;
+
+// How a run ended, which scripts can tell apart by git-clean's exit code. Fatal errors and
+// aborting at the prompt are errors instead, see `Error::exit_code`.
+#[derive(Debug, PartialEq)]
+pub enum Status {
+    Done,
+    NothingToClean,
+    PartialFailure,
+}
+
+impl Status {
+    pub fn exit_code(&self) -> i32 {
+        match *self {
+            Status::Done => 0,
+            Status::NothingToClean => EXIT_NOTHING_TO_CLEAN,
+            Status::PartialFailure => EXIT_PARTIAL_FAILURE,
+        }
+    }
+}
+
+// What happened to a ref git-clean tried to delete.
+#[derive(Debug, PartialEq)]
+pub enum Outcome {
+    Deleted,
+    // Someone else deleted it first, so there was nothing left to do.
+    AlreadyGone,
+    // The remote refused, because someone pushed to it since it was fetched or a hook declined.
+    Rejected(String),
+    Failed(String),
+}
+
+// The outcome of every ref a run tried to delete, locally and on the remote.
+#[derive(Debug, Default)]
+pub struct Report {
+    pub refs: Vec<(Entry, Outcome)>,
+}
+
+impl Report {
+    pub fn new(refs: Vec<(Entry, Outcome)>) -> Report {
+        Report { refs }
+    }
+
+    pub fn deleted(&self) -> Vec<Entry> {
+        self.refs
+            .iter()
+            .filter(|(_, outcome)| *outcome == Outcome::Deleted)
+            .map(|(entry, _)| entry.clone())
+            .collect()
+    }
+
+    pub fn failures(&self) -> usize {
+        self.refs
+            .iter()
+            .filter(|(_, outcome)| match *outcome {
+                Outcome::Rejected(_) | Outcome::Failed(_) => true,
+                Outcome::Deleted | Outcome::AlreadyGone => false,
+            })
+            .count()
+    }
+
+    pub fn status(&self) -> Status {
+        if self.failures() > 0 {
+            Status::PartialFailure
+        } else {
+            Status::Done
+        }
+    }
+
+    fn lines(&self, local: bool) -> Vec<String> {
+        self.refs
+            .iter()
+            .filter(|(entry, _)| entry.is_local() == local)
+            .map(|(entry, outcome)| describe(entry, outcome))
+            .collect()
+    }
+}
+
+// Lists the remote refs first and the local ones second, under headings when there are both.
+impl Display for Report {
+    fn fmt(&self, f: &mut Formatter) -> Result<(), FmtError> {
+        let remote = self.lines(false);
+        let local = self.lines(true);
+
+        match (remote.is_empty(), local.is_empty()) {
+            (false, false) => write!(
+                f,
+                "Remote:\n{}\n\nLocal:\n{}",
+                remote.join("\n"),
+                local.join("\n")
+            ),
+            (false, true) => write!(f, "{}", remote.join("\n")),
+            _ => write!(f, "{}", local.join("\n")),
+        }
+    }
+}
+
+// Describes what happened to a ref in the words git itself uses, where it has any.
+fn describe(entry: &Entry, outcome: &Outcome) -> String {
+    let tag = entry.git_ref.strip_prefix("refs/tags/");
+    let name = tag.unwrap_or_else(|| entry.git_ref.trim_start_matches("refs/heads/"));
+
+    match (entry.is_local(), outcome) {
+        (true, Outcome::Deleted) if tag.is_some() => {
+            format!("Deleted tag '{}' (was {}).", name, short_sha(&entry.sha))
+        }
+        (true, Outcome::Deleted) => {
+            format!("Deleted branch {} (was {}).", name, short_sha(&entry.sha))
+        }
+        (false, Outcome::Deleted) => format!(" - {:<17} {}", "[deleted]", name),
+        (true, Outcome::AlreadyGone) => format!("{} was already deleted.", entry.label()),
+        (false, Outcome::AlreadyGone) => format!("{} was already deleted in the remote.", name),
+        (_, Outcome::Rejected(reason)) => {
+            format!("{} was skipped, {}.", entry.label(), reason)
+        }
+        (_, Outcome::Failed(message)) => {
+            format!("Couldn't delete {}: {}", entry.label(), message)
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{Outcome, Report, Status};
+    use journal::Entry;
+
+    fn report() -> Report {
+        Report::new(vec![
+            (
+                Entry::remote(
+                    "origin",
+                    "refs/heads/merged".to_owned(),
+                    "4b8fb08".to_owned(),
+                ),
+                Outcome::Deleted,
+            ),
+            (
+                Entry::remote(
+                    "origin",
+                    "refs/heads/moved".to_owned(),
+                    "5c7ac19".to_owned(),
+                ),
+                Outcome::Rejected("someone pushed to it since it was fetched".to_owned()),
+            ),
+            (
+                Entry::remote("origin", "refs/tags/rc-1".to_owned(), "6d8bd2a".to_owned()),
+                Outcome::AlreadyGone,
+            ),
+            (
+                Entry::local("refs/heads/merged".to_owned(), "3a9ea97".to_owned()),
+                Outcome::Deleted,
+            ),
+            (
+                Entry::local("refs/tags/rc-1".to_owned(), "6d8bd2a".to_owned()),
+                Outcome::Failed("error: tag 'rc-1' is locked".to_owned()),
+            ),
+        ])
+    }
+
+    #[test]
+    fn test_report_display() {
+        assert_eq!(
+            "Remote:\n \
+             - [deleted]         merged\n\
+             origin/moved was skipped, someone pushed to it since it was fetched.\n\
+             rc-1 was already deleted in the remote.\n\
+             \n\
+             Local:\n\
+             Deleted branch merged (was 3a9ea97).\n\
+             Couldn't delete tag rc-1: error: tag 'rc-1' is locked",
+            report().to_string()
+        );
+    }
+
+    #[test]
+    fn test_report_status() {
+        let report = report();
+
+        assert_eq!(2, report.failures());
+        assert_eq!(Status::PartialFailure, report.status());
+        assert_eq!(
+            vec!["origin/merged", "merged"],
+            report
+                .deleted()
+                .iter()
+                .map(|e| e.label())
+                .collect::<Vec<String>>()
+        );
+        assert_eq!(Status::Done, Report::default().status());
+    }
+}
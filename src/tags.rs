@@ -0,0 +1,228 @@
+use branches::{group_by_reason, prompt, SelectionReason};
+use commands::*;
+use error::Error;
+use journal::Entry;
+use options::*;
+use std::collections::BTreeMap;
+
+// Why a tag was picked for deletion.
+#[derive(Clone, Debug, PartialEq)]
+pub enum TagReason {
+    // Tags that a kept release tag already contains, and tags created too long ago.
+    Released(String),
+    Created(u64),
+}
+
+impl SelectionReason for TagReason {
+    fn description(&self) -> String {
+        match *self {
+            TagReason::Released(ref tag) => format!("already in release {}", tag),
+            TagReason::Created(days) => format!("created over {} days ago", days),
+        }
+    }
+
+    // An old tag's commit doesn't have to be on any branch.
+    fn is_risky(&self) -> bool {
+        match *self {
+            TagReason::Released(_) => false,
+            TagReason::Created(_) => true,
+        }
+    }
+
+    fn rank(&self) -> u8 {
+        match *self {
+            TagReason::Released(_) => 0,
+            TagReason::Created(_) => 1,
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct Tag {
+    pub name: String,
+    pub reason: TagReason,
+    // The commit the tag points at, looking through annotated tags.
+    pub sha: String,
+    // What the tag ref itself points to, which is the tag object for annotated tags.
+    pub object: String,
+    // What the tag points to on the remote, when it's there too and gets deleted there.
+    pub remote: Option<String>,
+}
+
+impl Tag {
+    fn label(&self) -> String {
+        format!("{} ({})", self.name, short_sha(&self.sha))
+    }
+}
+
+#[derive(Debug)]
+pub struct Tags {
+    pub vec: Vec<Tag>,
+}
+
+impl Tags {
+    pub fn new(tags: Vec<Tag>) -> Tags {
+        Tags { vec: tags }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.vec.is_empty()
+    }
+
+    pub fn print_warning_and_prompt(&self, options: &Options) -> Result<(), Error> {
+        let warning = options.warning_message();
+        let labels = self.vec.iter().map(|t| (&t.reason, t.label())).collect();
+        prompt(&warning, &group_by_reason(labels))
+    }
+
+    // Tags matching --tags that a release tag already contains, or that were created before
+    // --older-than. Release tags themselves are always kept. Only local tags can be judged, so
+    // tags that are only on the remote are never picked, even with -r.
+    pub fn cleanable(options: &Options) -> Result<Tags, Error> {
+        // Tags aren't pruned like remote branches, so the remote is asked directly which ones it
+        // has. Fetching them would fail on tags that differ between here and there, and would add
+        // the remote's tags here even with -r.
+        let remote_tags = if let Local = options.delete_mode {
+            BTreeMap::new()
+        } else {
+            println!("Listing tags on {}", options.remote);
+            remote_refs(&options.remote, "refs/tags")?
+        };
+
+        let local_tags = local_tags()?;
+        let released_in = released_in(options, &local_tags)?;
+        let now = now();
+
+        let mut tags = vec![];
+        for tag in local_tags {
+            if !options.is_cleanable_tag(&tag.name) {
+                continue;
+            }
+
+            let reason = match (released_in.get(&tag.name), options.older_than) {
+                (Some(release), _) => TagReason::Released(release.clone()),
+                (None, Some(days)) if tag.created < now.saturating_sub(days * 24 * 60 * 60) => {
+                    TagReason::Created(days)
+                }
+                _ => continue,
+            };
+
+            // The reason only holds for the local tag, so a remote tag of the same name that
+            // points somewhere else is left alone.
+            let remote = match remote_tags.get(&format!("refs/tags/{}", tag.name)) {
+                Some(object) if *object != tag.object => {
+                    println!(
+                        "Keeping {} on {}, it points to {} there instead of {}.",
+                        tag.name,
+                        options.remote,
+                        short_sha(object),
+                        short_sha(&tag.object)
+                    );
+                    None
+                }
+                remote => remote.cloned(),
+            };
+            if let Remote = options.delete_mode {
+                if remote.is_none() {
+                    continue;
+                }
+            }
+
+            tags.push(Tag {
+                name: tag.name,
+                reason,
+                sha: tag.commit,
+                object: tag.object,
+                remote,
+            });
+        }
+
+        Ok(Tags::new(tags))
+    }
+
+    // Like `Branches::refs_to_delete`, the local and remote copy of every tag.
+    pub fn refs_to_delete(&self, options: &Options) -> Vec<(Entry, &TagReason)> {
+        let mut entries = vec![];
+
+        for tag in &self.vec {
+            let git_ref = format!("refs/tags/{}", tag.name);
+            if options.delete_mode.deletes_local() {
+                entries.push((
+                    Entry::local(git_ref.clone(), tag.object.clone()),
+                    &tag.reason,
+                ));
+            }
+            if let Some(ref object) = tag.remote {
+                if options.delete_mode.deletes_remote() {
+                    entries.push((
+                        Entry::remote(&options.remote, git_ref, object.clone()),
+                        &tag.reason,
+                    ));
+                }
+            }
+        }
+
+        entries
+    }
+}
+
+struct LocalTag {
+    name: String,
+    commit: String,
+    object: String,
+    created: u64,
+}
+
+// Every local tag, oldest first. Annotated tags have their own creation date, lightweight tags
+// use their commit's.
+fn local_tags() -> Result<Vec<LocalTag>, Error> {
+    let refs = output(&[
+        "git",
+        "for-each-ref",
+        "--sort=creatordate",
+        "--format=%(refname:lstrip=2) %(creatordate:unix) %(objectname) %(*objectname)",
+        "refs/tags",
+    ])?;
+
+    Ok(refs
+        .lines()
+        .filter_map(|line| {
+            let mut pieces = line.split(' ');
+            match (pieces.next(), pieces.next(), pieces.next(), pieces.next()) {
+                (Some(name), Some(created), Some(object), peeled) => Some(LocalTag {
+                    name: name.to_owned(),
+                    commit: peeled
+                        .filter(|p| !p.is_empty())
+                        .unwrap_or(object)
+                        .to_owned(),
+                    object: object.to_owned(),
+                    created: created.parse().ok()?,
+                }),
+                _ => None,
+            }
+        })
+        .collect())
+}
+
+// Maps every cleanable tag to the oldest release tag that contains its commit.
+fn released_in(options: &Options, tags: &[LocalTag]) -> Result<BTreeMap<String, String>, Error> {
+    let mut released_in = BTreeMap::new();
+
+    for release in tags.iter().filter(|t| options.is_release_tag(&t.name)) {
+        let contained = output(&[
+            "git",
+            "for-each-ref",
+            "--format=%(refname:lstrip=2)",
+            "--merged",
+            &format!("refs/tags/{}", release.name),
+            "refs/tags",
+        ])?;
+        for tag in contained.lines().filter(|t| options.is_cleanable_tag(t)) {
+            released_in
+                .entry(tag.to_owned())
+                .or_insert_with(|| release.name.clone());
+        }
+    }
+
+    Ok(released_in)
+}
@@ -0,0 +1,149 @@
+use support::project;
+
+#[test]
+fn test_git_clean_archives_branches_before_deleting_them() {
+    let project = project("git-clean_archives_branches")
+        .build()
+        .setup_remote();
+
+    project.batch_setup_commands(&[
+        "git checkout -b test1",
+        "git push origin HEAD",
+        "git checkout main",
+        "git branch test2",
+    ]);
+
+    let result = project
+        .git_clean_command("-y --archive refs --archive-remote")
+        .run();
+
+    assert!(
+        result.is_success(),
+        "{}",
+        result.failure_message("command to succeed")
+    );
+    assert!(
+        result
+            .stdout()
+            .contains("Archived test1 to refs/archive/test1\nArchived origin/test1 to refs/archive/test1\nArchived test2 to refs/archive/test2"),
+        "{}",
+        result.failure_message("command to archive test1 and test2")
+    );
+    assert!(
+        result.stdout().contains("Deleted branch test1")
+            && result.stdout().contains(" - [deleted]         test1"),
+        "{}",
+        result.failure_message("command to delete test1 locally and remotely")
+    );
+
+    let archives = project.setup_command("git for-each-ref --format=%(refname) refs/archive");
+    let remote_archives = project.setup_command("git ls-remote origin refs/archive/*");
+
+    assert!(
+        archives.stdout() == "refs/archive/test1\nrefs/archive/test2\n",
+        "{}",
+        archives.failure_message("test1 and test2 to be archived")
+    );
+    assert!(
+        remote_archives.stdout().contains("refs/archive/test1"),
+        "{}",
+        remote_archives.failure_message("test1 to be archived on the remote")
+    );
+}
+
+#[test]
+fn test_git_clean_archives_branches_as_tags() {
+    let project = project("git-clean_archives_branches_as_tags").build();
+
+    project.setup_command("git branch test1");
+
+    let result = project.git_clean_command("-y -l --archive tags").run();
+
+    assert!(
+        result
+            .stdout()
+            .contains("Archived test1 to refs/tags/archive/test1")
+            && result.stdout().contains("Deleted branch test1"),
+        "{}",
+        result.failure_message("command to archive and delete test1")
+    );
+
+    let tags = project.setup_command("git tag");
+
+    assert!(
+        tags.stdout().contains("archive/test1"),
+        "{}",
+        tags.failure_message("the archive/test1 tag to exist")
+    );
+}
+
+#[test]
+fn test_git_clean_keeps_branches_it_cannot_archive() {
+    let project = project("git-clean_keeps_unarchivable_branches").build();
+
+    project.batch_setup_commands(&["git branch test1", "git update-ref refs/archive/test1 HEAD"]);
+
+    let result = project.git_clean_command("-y -l --archive refs").run();
+
+    assert!(
+        result
+            .stdout()
+            .contains("Couldn't archive test1, refs/archive/test1 already exists."),
+        "{}",
+        result.failure_message("command to report the existing archive")
+    );
+    assert!(
+        !result.stdout().contains("Deleted branch test1"),
+        "{}",
+        result.failure_message("command not to delete test1")
+    );
+}
+
+#[test]
+fn test_git_clean_lists_and_restores_archived_branches() {
+    let project = project("git-clean_restores_archived_branches").build();
+
+    project.batch_setup_commands(&["git branch test1", "git branch test2"]);
+    project.git_clean_command("-y -l --archive refs").run();
+
+    let list = project.git_clean_command("archived").run();
+
+    assert!(
+        list.stdout().contains("test1 (") && list.stdout().contains(", refs/archive/test2)"),
+        "{}",
+        list.failure_message("command to list test1 and test2")
+    );
+
+    let restore = project
+        .git_clean_command("archived --restore test1 --restore missing")
+        .run();
+
+    assert!(
+        restore
+            .stdout()
+            .contains("Restored test1 from refs/archive/test1 (was "),
+        "{}",
+        restore.failure_message("command to restore test1")
+    );
+    assert!(
+        restore
+            .stdout()
+            .contains("Couldn't find an archive of missing."),
+        "{}",
+        restore.failure_message("command to report the missing archive")
+    );
+
+    let branches = project.setup_command("git branch");
+    let archives = project.setup_command("git for-each-ref --format=%(refname) refs/archive");
+
+    assert!(
+        branches.stdout().contains("test1") && !branches.stdout().contains("test2"),
+        "{}",
+        branches.failure_message("only test1 to be back")
+    );
+    assert!(
+        archives.stdout() == "refs/archive/test2\n",
+        "{}",
+        archives.failure_message("only test2 to still be archived")
+    );
+}
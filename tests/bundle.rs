@@ -0,0 +1,98 @@
+use support::project;
+
+#[test]
+fn test_git_clean_bundles_branches_before_deleting_them() {
+    let project = project("git-clean_bundles_branches").build().setup_remote();
+
+    project.batch_setup_commands(&[
+        "git checkout -b test1",
+        "git push origin HEAD",
+        "git checkout main",
+        "git branch test2",
+    ]);
+    let bundle = project.path().join("backup.bundle");
+    let bundle = bundle.to_str().unwrap();
+
+    let result = project
+        .git_clean_command(&format!("-y --bundle {}", bundle))
+        .run();
+
+    assert!(
+        result.is_success(),
+        "{}",
+        result.failure_message("command to succeed")
+    );
+    assert!(
+        result
+            .stdout()
+            .contains(&format!("Bundled 3 refs to {}", bundle)),
+        "{}",
+        result.failure_message("command to bundle test1, origin/test1 and test2")
+    );
+
+    let heads = project.setup_command(&format!("git bundle list-heads {}", bundle));
+
+    assert!(
+        heads.stdout().contains("refs/heads/test1")
+            && heads.stdout().contains("refs/remotes/origin/test1")
+            && heads.stdout().contains("refs/heads/test2"),
+        "{}",
+        heads.failure_message("the bundle to hold every deleted ref")
+    );
+
+    let restore = project
+        .git_clean_command(&format!("undo --bundle {}", bundle))
+        .run();
+
+    assert!(
+        restore.is_success(),
+        "{}",
+        restore.failure_message("command to succeed")
+    );
+    assert!(
+        restore.stdout().contains("Restored test1 (was ")
+            && restore.stdout().contains("Restored test2 (was ")
+            && restore.stdout().contains("Restored origin/test1 (was "),
+        "{}",
+        restore.failure_message("command to restore test1 and test2, and push test1 back")
+    );
+
+    let remote_branches = project.setup_command("git ls-remote --heads origin");
+
+    assert!(
+        remote_branches.stdout().contains("refs/heads/test1"),
+        "{}",
+        remote_branches.failure_message("test1 to be back on the remote")
+    );
+}
+
+#[test]
+fn test_git_clean_does_not_delete_anything_without_a_bundle() {
+    let project = project("git-clean_needs_a_bundle").build();
+
+    project.setup_command("git branch test1");
+    let bundle = project.path().join("missing").join("backup.bundle");
+
+    let result = project
+        .git_clean_command(&format!("-y --bundle {}", bundle.to_str().unwrap()))
+        .run();
+
+    assert!(
+        !result.is_success(),
+        "{}",
+        result.failure_message("command to fail")
+    );
+    assert!(
+        result.stdout().contains("so nothing was deleted"),
+        "{}",
+        result.failure_message("command to report the bundle failure")
+    );
+
+    let branches = project.setup_command("git branch");
+
+    assert!(
+        branches.stdout().contains("test1"),
+        "{}",
+        branches.failure_message("test1 to be kept")
+    );
+}
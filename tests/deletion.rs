@@ -88,10 +88,11 @@ fn git_clean_does_not_delete_branches_ahead_of_main(flags: &str) {
 
     let result = project.git_clean_command(flags).run();
 
-    assert!(
-        result.is_success(),
+    assert_eq!(
+        Some(2),
+        result.exit_code(),
         "{}",
-        result.failure_message("command to succeed")
+        result.failure_message("command to have nothing to clean")
     );
     assert!(
         !result.stdout().contains("Deleted branch ahead"),
@@ -125,10 +126,11 @@ fn git_clean_with_unpushed_ahead_branch(flags: &str, expect_branch_deleted: bool
 
     let result = project.git_clean_command(flags).run();
 
-    assert!(
-        result.is_success(),
+    assert_eq!(
+        Some(if expect_branch_deleted { 0 } else { 2 }),
+        result.exit_code(),
         "{}",
-        result.failure_message("command to succeed")
+        result.failure_message("command to have nothing to clean")
     );
     if expect_branch_deleted {
         assert!(
@@ -151,8 +153,60 @@ fn test_git_clean_does_not_delete_unpushed_ahead_branch() {
 }
 
 #[test]
-fn test_git_clean_deletes_unpushed_ahead_branch() {
-    git_clean_with_unpushed_ahead_branch("-y -d", true)
+fn test_git_clean_refuses_unpushed_ahead_branch() {
+    git_clean_with_unpushed_ahead_branch("-y -d", false)
+}
+
+#[test]
+fn test_git_clean_deletes_unpushed_ahead_branch_with_force() {
+    git_clean_with_unpushed_ahead_branch("-y -d --force", true)
+}
+
+#[test]
+fn test_git_clean_lists_commits_that_would_be_lost() {
+    let project = project("git-clean_unmerged_commits").build().setup_remote();
+
+    project.batch_setup_commands(&[
+        "git checkout -b unpushed",
+        "git commit --allow-empty -m Unpushed",
+        "git checkout -b kept",
+        "git commit --allow-empty -m Kept",
+        "git push origin HEAD",
+        "git checkout -b covered main",
+        "git commit --allow-empty -m Covered",
+        "git branch covered_too",
+        "git checkout main",
+    ]);
+
+    let result = project.git_clean_command("-y -d").run();
+
+    assert!(
+        result.is_success(),
+        "{}",
+        result.failure_message("command to succeed")
+    );
+    assert!(
+        result.stdout().contains(
+            "Refusing to delete covered, it has 1 commit nothing else has (use --force to delete it anyway):\n  "
+        ),
+        "{}",
+        result.failure_message("command to refuse covered, since covered_too goes too")
+    );
+    assert!(
+        result.stdout().contains(" Covered\n"),
+        "{}",
+        result.failure_message("command to list the commit that would be lost")
+    );
+    assert!(
+        result.stdout().contains("Deleted branch unpushed"),
+        "{}",
+        result.failure_message("command to delete unpushed, since kept has its commit")
+    );
+    assert!(
+        !result.stdout().contains("Deleted branch covered"),
+        "{}",
+        result.failure_message("command not to delete covered")
+    );
 }
 
 #[test]
@@ -236,10 +290,11 @@ fn test_git_clean_ignores_squashes_without_flag() {
 
     let result = project.git_clean_command("-y").run();
 
-    assert!(
-        result.is_success(),
+    assert_eq!(
+        Some(2),
+        result.exit_code(),
         "{}",
-        result.failure_message("command to succeed")
+        result.failure_message("command to have nothing to clean")
     );
     assert!(
         !result
@@ -254,3 +309,303 @@ fn test_git_clean_ignores_squashes_without_flag() {
         result.failure_message("command not to delete github_squash locally")
     );
 }
+
+#[test]
+fn test_git_clean_squashes_check_branches_not_tags_with_the_same_name() {
+    let project = project("git-clean_squashes_branch_and_tag")
+        .build()
+        .setup_remote();
+
+    let touch_squash_command = touch_command!(project, "squash.txt");
+    let touch_unmerged_command = touch_command!(project, "unmerged.txt");
+
+    // The tag points at the part of the branch that made it into main, the branch itself has work
+    // on top that didn't.
+    project.batch_setup_commands(&[
+        "git checkout -b feature",
+        &touch_squash_command,
+        "git add .",
+        "git commit -am Commit",
+        "git tag feature",
+        &touch_unmerged_command,
+        "git add .",
+        "git commit -am Unmerged",
+        "git push origin HEAD",
+        "git checkout main",
+        &touch_squash_command,
+        "git add .",
+        "git commit -am Squash",
+        "git push origin HEAD",
+    ]);
+
+    let result = project.git_clean_command("-y --squashes --rebases").run();
+
+    assert_eq!(
+        Some(2),
+        result.exit_code(),
+        "{}",
+        result.failure_message("command to have nothing to clean")
+    );
+    assert!(
+        !result.stdout().contains("Deleted branch feature"),
+        "{}",
+        result.failure_message("command not to delete feature")
+    );
+}
+
+#[test]
+fn test_git_clean_squashes_leave_the_working_tree_alone() {
+    let project = project("git-clean_squashes_dirty_tree")
+        .build()
+        .setup_remote();
+
+    let touch_squash_command = touch_command!(project, "squash.txt");
+    let touch_new_command = touch_command!(project, "new.txt");
+    let touch_dirty_command = touch_command!(project, "dirty.txt");
+
+    project.batch_setup_commands(&[
+        "git checkout -b github_squash",
+        &touch_squash_command,
+        "git add .",
+        "git commit -am Commit",
+        "git push origin HEAD",
+        "git checkout -b unmerged",
+        &touch_new_command,
+        "git add .",
+        "git commit -am Unmerged",
+        "git checkout main",
+        &touch_squash_command,
+        "git add .",
+        "git commit -am Squash",
+        "git push origin HEAD",
+        &touch_dirty_command,
+        "git add dirty.txt",
+    ]);
+
+    let head_before = project.setup_command("git rev-parse HEAD");
+    let unmerged_before = project.setup_command("git rev-parse unmerged");
+
+    let result = project.git_clean_command("-y -l --squashes").run();
+
+    assert!(
+        result.is_success(),
+        "{}",
+        result.failure_message("command to succeed")
+    );
+    assert!(
+        result.stdout().contains("Deleted branch github_squash"),
+        "{}",
+        result.failure_message("command to delete github_squash locally")
+    );
+    assert!(
+        !result.stdout().contains("Deleted branch unmerged"),
+        "{}",
+        result.failure_message("command not to delete unmerged")
+    );
+
+    let current_branch = project.setup_command("git rev-parse --abbrev-ref HEAD");
+    assert_eq!("main", current_branch.stdout().trim());
+
+    let head_after = project.setup_command("git rev-parse HEAD");
+    assert_eq!(head_before.stdout(), head_after.stdout());
+
+    let unmerged_after = project.setup_command("git rev-parse unmerged");
+    assert_eq!(unmerged_before.stdout(), unmerged_after.stdout());
+
+    let status = project.setup_command("git status --porcelain");
+    assert!(
+        status.stdout().contains("A  dirty.txt"),
+        "{}",
+        status.failure_message("staged changes to be left alone")
+    );
+}
+
+#[test]
+fn test_git_clean_squashes_leave_a_detached_head_alone() {
+    let project = project("git-clean_squashes_detached_head")
+        .build()
+        .setup_remote();
+
+    let touch_squash_command = touch_command!(project, "squash.txt");
+
+    project.batch_setup_commands(&[
+        "git checkout -b github_squash",
+        &touch_squash_command,
+        "git add .",
+        "git commit -am Commit",
+        "git push origin HEAD",
+        "git checkout main",
+        &touch_squash_command,
+        "git add .",
+        "git commit -am Squash",
+        "git push origin HEAD",
+        "git checkout --detach HEAD~1",
+    ]);
+
+    let head_before = project.setup_command("git rev-parse HEAD");
+
+    let result = project.git_clean_command("-y -l --squashes").run();
+
+    assert!(
+        result.is_success(),
+        "{}",
+        result.failure_message("command to succeed")
+    );
+    assert!(
+        result.stdout().contains("Deleted branch github_squash"),
+        "{}",
+        result.failure_message("command to delete github_squash locally")
+    );
+
+    let current_branch = project.setup_command("git rev-parse --abbrev-ref HEAD");
+    assert_eq!("HEAD", current_branch.stdout().trim());
+
+    let head_after = project.setup_command("git rev-parse HEAD");
+    assert_eq!(head_before.stdout(), head_after.stdout());
+}
+
+fn git_clean_with_rebase_merged_branch(flags: &str, expect_branch_deleted: bool) {
+    let project = project("git-clean_github_rebases").build().setup_remote();
+
+    let touch_first_command = touch_command!(project, "first.txt");
+    let touch_second_command = touch_command!(project, "second.txt");
+    let touch_new_command = touch_command!(project, "new.txt");
+
+    // Github's "Rebase and merge" replays each commit of the branch on top of main, so every
+    // commit lands on main with a new SHA and the branch is never an ancestor of main.
+    project.batch_setup_commands(&[
+        "git checkout -b github_rebase",
+        &touch_first_command,
+        "git add .",
+        "git commit -am First",
+        &touch_second_command,
+        "git add .",
+        "git commit -am Second",
+        "git push origin HEAD",
+        "git checkout main",
+        &touch_new_command,
+        "git add .",
+        "git commit -am Other",
+        "git cherry-pick main..github_rebase",
+        "git push origin HEAD",
+    ]);
+
+    let result = project.git_clean_command(flags).run();
+
+    assert_eq!(
+        Some(if expect_branch_deleted { 0 } else { 2 }),
+        result.exit_code(),
+        "{}",
+        result.failure_message("command to have nothing to clean")
+    );
+    if expect_branch_deleted {
+        assert!(
+            result.stdout().contains("Deleted branch github_rebase"),
+            "{}",
+            result.failure_message("command to delete github_rebase")
+        );
+    } else {
+        assert!(
+            !result.stdout().contains("Deleted branch github_rebase"),
+            "{}",
+            result.failure_message("command not to delete github_rebase")
+        );
+    }
+}
+
+#[test]
+fn test_git_clean_works_with_rebases_with_flag() {
+    git_clean_with_rebase_merged_branch("-y --rebases", true)
+}
+
+#[test]
+fn test_git_clean_ignores_rebases_without_flag() {
+    git_clean_with_rebase_merged_branch("-y", false)
+}
+
+#[test]
+fn test_git_clean_deletes_stale_branches() {
+    let project = project("git-clean_stale_branches").build().setup_remote();
+
+    let old_commit = [("GIT_COMMITTER_DATE", "2000-01-01T00:00:00")];
+
+    project.batch_setup_commands(&["git checkout -b old_local"]);
+    project.setup_command_with_env("git commit --allow-empty -m Old", &old_commit);
+    project.batch_setup_commands(&["git checkout -b old_pushed"]);
+    project.setup_command_with_env("git commit --allow-empty -m Old", &old_commit);
+    project.batch_setup_commands(&[
+        "git push origin HEAD",
+        "git checkout -b old_remote",
+        "git push origin HEAD",
+        "git checkout -b fresh",
+        "git commit --allow-empty -m Fresh",
+        "git push origin HEAD",
+        "git checkout main",
+        "git branch -D old_remote",
+    ]);
+
+    let result = project.git_clean_command("-y --older-than 90d").run();
+
+    assert!(
+        result.is_success(),
+        "{}",
+        result.failure_message("command to succeed")
+    );
+    assert!(
+        result.stdout().contains("Deleted branch old_local"),
+        "{}",
+        result.failure_message("command to delete old_local")
+    );
+    assert!(
+        result.stdout().contains("Deleted branch old_pushed"),
+        "{}",
+        result.failure_message("command to delete old_pushed locally")
+    );
+    assert!(
+        result.stdout().contains(" - [deleted]         old_pushed"),
+        "{}",
+        result.failure_message("command to delete old_pushed in remote")
+    );
+    assert!(
+        result.stdout().contains(" - [deleted]         old_remote"),
+        "{}",
+        result.failure_message("command to delete old_remote in remote")
+    );
+    assert!(
+        !result.stdout().contains("fresh"),
+        "{}",
+        result.failure_message("command not to delete fresh")
+    );
+}
+
+#[test]
+fn test_git_clean_lists_stale_branches_separately() {
+    let project = project("git-clean_stale_branches_prompt").build();
+
+    project.batch_setup_commands(&["git branch merged", "git checkout -b old"]);
+    project.setup_command_with_env(
+        "git commit --allow-empty -m Old",
+        &[("GIT_COMMITTER_DATE", "2000-01-01T00:00:00")],
+    );
+    project.setup_command("git checkout main");
+
+    let result = project
+        .git_clean_command("-l --older-than 90d --force")
+        .stdin("n\n")
+        .run();
+
+    assert!(
+        result.stdout().contains(
+            "The following branches will be deleted locally:\n\nMerged into main:\nmerged ("
+        ),
+        "{}",
+        result.failure_message("merged branches to be listed first")
+    );
+    assert!(
+        result
+            .stdout()
+            .contains("\n\nNo commits in over 90 days, may hold unmerged work:\nold ("),
+        "{}",
+        result.failure_message("stale branches to be listed separately")
+    );
+}
@@ -1,5 +1,20 @@
+use std::fs;
 use support::project;
 
+macro_rules! touch_command {
+    ($project:ident, $file_name:literal) => {
+        if cfg!(windows) {
+            format!(
+                "cmd /c copy nul {}\\{}",
+                $project.path().display(),
+                $file_name
+            )
+        } else {
+            format!("touch {}", $file_name)
+        }
+    };
+}
+
 #[test]
 fn test_git_clean_removes_local_branches() {
     let project = project("git-clean_removes_local").build();
@@ -127,3 +142,440 @@ fn test_git_clean_does_not_remove_list_of_ignored_local_branches() {
         result.failure_message("command to delete test3")
     );
 }
+
+#[test]
+fn test_git_clean_does_not_remove_branches_matching_ignored_patterns() {
+    let project = project("git-clean_ignores_patterns").build();
+
+    project.batch_setup_commands(&[
+        "git branch release/1.0",
+        "git branch hotfix/a/b",
+        "git branch keep-me",
+        "git branch feature/keep-me",
+    ]);
+
+    let result = project
+        .git_clean_command("-y -i release/* -i hotfix/** -i ^keep-.*$")
+        .run();
+
+    assert!(
+        result.is_success(),
+        "{}",
+        result.failure_message("command to succeed")
+    );
+    assert!(
+        !result.stdout().contains("Deleted branch release/1.0"),
+        "{}",
+        result.failure_message("command not to delete release/1.0")
+    );
+    assert!(
+        !result.stdout().contains("Deleted branch hotfix/a/b"),
+        "{}",
+        result.failure_message("command not to delete hotfix/a/b")
+    );
+    assert!(
+        !result.stdout().contains("Deleted branch keep-me"),
+        "{}",
+        result.failure_message("command not to delete keep-me")
+    );
+    assert!(
+        result.stdout().contains("Deleted branch feature/keep-me"),
+        "{}",
+        result.failure_message("command to delete feature/keep-me")
+    );
+}
+
+#[test]
+fn test_git_clean_only_removes_included_branches() {
+    let project = project("git-clean_includes_patterns").build();
+
+    project.batch_setup_commands(&[
+        "git branch feature/login",
+        "git branch feature/keep",
+        "git branch dependabot/cargo/regex",
+        "git branch fix-login",
+    ]);
+
+    let result = project
+        .git_clean_command("-y --include feature/* --include dependabot/** -i feature/keep")
+        .run();
+
+    assert!(
+        result.is_success(),
+        "{}",
+        result.failure_message("command to succeed")
+    );
+    assert!(
+        result.stdout().contains("Deleted branch feature/login"),
+        "{}",
+        result.failure_message("command to delete feature/login")
+    );
+    assert!(
+        result
+            .stdout()
+            .contains("Deleted branch dependabot/cargo/regex"),
+        "{}",
+        result.failure_message("command to delete dependabot/cargo/regex")
+    );
+    assert!(
+        !result.stdout().contains("Deleted branch feature/keep"),
+        "{}",
+        result.failure_message("command not to delete the ignored feature/keep")
+    );
+    assert!(
+        !result.stdout().contains("Deleted branch fix-login"),
+        "{}",
+        result.failure_message("command not to delete fix-login")
+    );
+}
+
+#[test]
+fn test_git_clean_rejects_invalid_ignored_patterns() {
+    let project = project("git-clean_rejects_invalid_patterns").build();
+
+    let result = project.git_clean_command("-y -i ^keep-(.*$").run();
+
+    assert!(
+        !result.is_success(),
+        "{}",
+        result.failure_message("command to fail")
+    );
+}
+
+#[test]
+fn test_git_clean_runs_from_any_branch() {
+    let project = project("git-clean_runs_from_any_branch").build();
+
+    project.batch_setup_commands(&[
+        "git branch merged",
+        "git checkout -b wip",
+        "git commit --allow-empty -m Wip",
+    ]);
+
+    let result = project.git_clean_command("-y").run();
+
+    assert!(
+        result.is_success(),
+        "{}",
+        result.failure_message("command to succeed")
+    );
+    assert!(
+        result.stdout().contains("Deleted branch merged"),
+        "{}",
+        result.failure_message("command to delete merged")
+    );
+    assert!(
+        !result.stdout().contains("Deleted branch wip"),
+        "{}",
+        result.failure_message("command not to delete wip")
+    );
+}
+
+#[test]
+fn test_git_clean_does_not_remove_the_current_branch() {
+    let project = project("git-clean_keeps_current_branch").build();
+
+    project.batch_setup_commands(&["git branch merged", "git checkout -b current"]);
+
+    let result = project.git_clean_command("-y").run();
+
+    assert!(
+        result.is_success(),
+        "{}",
+        result.failure_message("command to succeed")
+    );
+    assert!(
+        result.stdout().contains("Deleted branch merged"),
+        "{}",
+        result.failure_message("command to delete merged")
+    );
+    assert!(
+        !result.stdout().contains("Deleted branch current"),
+        "{}",
+        result.failure_message("command not to delete current")
+    );
+}
+
+#[test]
+fn test_git_clean_uses_every_base_branch() {
+    let project = project("git-clean_multiple_base_branches").build();
+
+    project.batch_setup_commands(&[
+        "git checkout -b release/1.0",
+        "git commit --allow-empty -m Release",
+        "git checkout -b fix",
+        "git commit --allow-empty -m Fix",
+        "git checkout release/1.0",
+        "git merge fix",
+        "git checkout -b unmerged",
+        "git commit --allow-empty -m Unmerged",
+        "git checkout main",
+        "git branch merged",
+    ]);
+
+    let prompt = project
+        .git_clean_command("-b main -b release/*")
+        .stdin("n\n")
+        .run();
+
+    assert!(
+        prompt.stdout().contains("Merged into release/1.0:\nfix ("),
+        "{}",
+        prompt.failure_message("fix to be listed as merged into release/1.0")
+    );
+    assert!(
+        prompt.stdout().contains("Merged into main:\nmerged ("),
+        "{}",
+        prompt.failure_message("merged to be listed as merged into main")
+    );
+
+    let result = project.git_clean_command("-y -b main -b release/*").run();
+
+    assert!(
+        result.is_success(),
+        "{}",
+        result.failure_message("command to succeed")
+    );
+    assert!(
+        result.stdout().contains("Deleted branch fix"),
+        "{}",
+        result.failure_message("command to delete fix")
+    );
+    assert!(
+        result.stdout().contains("Deleted branch merged"),
+        "{}",
+        result.failure_message("command to delete merged")
+    );
+    assert!(
+        !result.stdout().contains("Deleted branch release/1.0"),
+        "{}",
+        result.failure_message("command not to delete release/1.0")
+    );
+    assert!(
+        !result.stdout().contains("Deleted branch unmerged"),
+        "{}",
+        result.failure_message("command not to delete unmerged")
+    );
+}
+
+#[test]
+fn test_git_clean_skips_branches_checked_out_in_worktrees() {
+    let project = project("git-clean_skips_worktree_branches").build();
+
+    project.batch_setup_commands(&[
+        "git branch merged",
+        "git worktree add worktree -b in_worktree",
+    ]);
+
+    let result = project.git_clean_command("-y").run();
+
+    assert!(
+        result.is_success(),
+        "{}",
+        result.failure_message("command to succeed")
+    );
+    assert!(
+        result.stdout().contains("Deleted branch merged"),
+        "{}",
+        result.failure_message("command to delete merged")
+    );
+    assert!(
+        !result.stdout().contains("in_worktree"),
+        "{}",
+        result.failure_message("command not to touch in_worktree")
+    );
+}
+
+#[test]
+fn test_git_clean_prunes_clean_worktrees() {
+    let project = project("git-clean_prunes_worktrees").build();
+
+    let touch_dirty_command = touch_command!(project, "dirty_worktree/dirty.txt");
+
+    project.batch_setup_commands(&[
+        "git worktree add clean_worktree -b clean",
+        "git worktree add dirty_worktree -b dirty",
+        &touch_dirty_command,
+    ]);
+
+    let result = project.git_clean_command("-y --prune-worktrees").run();
+
+    assert!(
+        result.is_success(),
+        "{}",
+        result.failure_message("command to succeed")
+    );
+    assert!(
+        result.stdout().contains("Removed worktree"),
+        "{}",
+        result.failure_message("command to remove clean_worktree")
+    );
+    assert!(
+        result.stdout().contains("Deleted branch clean"),
+        "{}",
+        result.failure_message("command to delete clean")
+    );
+    assert!(
+        !result.stdout().contains("Deleted branch dirty"),
+        "{}",
+        result.failure_message("command not to delete dirty")
+    );
+
+    let worktrees = project.setup_command("git worktree list");
+    assert!(
+        !worktrees.stdout().contains("clean_worktree"),
+        "{}",
+        worktrees.failure_message("clean_worktree to be removed")
+    );
+    assert!(
+        worktrees.stdout().contains("dirty_worktree"),
+        "{}",
+        worktrees.failure_message("dirty_worktree to be kept")
+    );
+}
+
+#[test]
+fn test_git_clean_interactive_needs_a_terminal() {
+    let project = project("git-clean_interactive_terminal").build();
+
+    project.setup_command("git branch merged");
+
+    let result = project.git_clean_command("-l --interactive").run();
+
+    assert_eq!(
+        Some(1),
+        result.exit_code(),
+        "{}",
+        result.failure_message("command to fail")
+    );
+    assert!(
+        result.stdout().contains(
+            "--interactive needs a terminal, please leave it out to be prompted instead."
+        ),
+        "{}",
+        result.failure_message("command to ask for a terminal")
+    );
+
+    let branches = project.setup_command("git branch");
+
+    assert!(
+        branches.stdout().contains("merged"),
+        "{}",
+        branches.failure_message("merged to be kept")
+    );
+}
+
+#[test]
+fn test_git_clean_steps_through_branches() {
+    let project = project("git-clean_steps_through_branches")
+        .build()
+        .setup_remote();
+
+    project.batch_setup_commands(&[
+        "git branch a1",
+        "git branch a2",
+        "git branch a3",
+        "git branch a4",
+    ]);
+
+    let result = project
+        .git_clean_command("-l --step")
+        .stdin("?\ny\nl\nn\nmaybe\nq\n")
+        .run();
+
+    assert!(
+        result.is_success(),
+        "{}",
+        result.failure_message("command to succeed")
+    );
+    assert!(
+        result.stdout().contains("Merged into main:\nDelete a1 ("),
+        "{}",
+        result.failure_message("command to ask about a1 first")
+    );
+    assert!(
+        result.stdout().contains("l - show the commits"),
+        "{}",
+        result.failure_message("command to print help")
+    );
+    assert!(
+        result
+            .stdout()
+            .contains("Every commit is on a base branch already."),
+        "{}",
+        result.failure_message("command to show a2's log")
+    );
+    assert!(
+        result.stdout().contains("Deleted branch a1"),
+        "{}",
+        result.failure_message("command to delete a1")
+    );
+    for kept in &["a2", "a3", "a4"] {
+        assert!(
+            !result
+                .stdout()
+                .contains(&format!("Deleted branch {}", kept)),
+            "{}",
+            result.failure_message(&format!("command to keep {}", kept))
+        );
+    }
+
+    let all = project.git_clean_command("-l --step").stdin("a\n").run();
+
+    assert!(
+        all.stdout().contains("Deleted branch a2")
+            && all.stdout().contains("Deleted branch a3")
+            && all.stdout().contains("Deleted branch a4"),
+        "{}",
+        all.failure_message("command to delete the remaining branches")
+    );
+
+    let none = project.git_clean_command("-l --step").stdin("").run();
+
+    assert_eq!(
+        Some(2),
+        none.exit_code(),
+        "{}",
+        none.failure_message("command to have nothing to clean")
+    );
+}
+
+#[test]
+fn test_git_clean_reports_branches_deleted_by_someone_else() {
+    let project = project("git-clean_already_deleted").build();
+
+    project.batch_setup_commands(&["git branch a1", "git branch a2"]);
+
+    // Deleting a1 takes a2 with it, so a2 is gone by the time git-clean gets to it.
+    let hook = project.path().join(".git/hooks/reference-transaction");
+    fs::write(
+        &hook,
+        "#!/bin/sh\n\
+         if [ \"$1\" = committed ] && grep -q ' refs/heads/a1$'; then\n\
+         \x20 git update-ref -d refs/heads/a2\n\
+         fi\n",
+    )
+    .unwrap();
+    if !cfg!(windows) {
+        project.setup_command("chmod +x .git/hooks/reference-transaction");
+    }
+
+    let result = project.git_clean_command("-y -l").run();
+
+    assert_eq!(
+        Some(0),
+        result.exit_code(),
+        "{}",
+        result.failure_message("command to succeed")
+    );
+    assert!(
+        result.stdout().contains("Deleted branch a1"),
+        "{}",
+        result.failure_message("command to delete a1")
+    );
+    assert!(
+        result.stdout().contains("a2 was already deleted."),
+        "{}",
+        result.failure_message("command to report a2 as already deleted")
+    );
+}
@@ -0,0 +1,98 @@
+use std::fs;
+use support::project;
+
+#[test]
+fn test_git_clean_applies_plans_to_refs_that_did_not_move() {
+    let project = project("git-clean_applies_plans").build().setup_remote();
+
+    project.batch_setup_commands(&[
+        "git checkout -b test1",
+        "git push origin HEAD",
+        "git checkout main",
+        "git branch test2",
+    ]);
+    let plan = project.path().join("plan.json");
+    let plan = plan.to_str().unwrap();
+
+    let result = project.git_clean_command(&format!("--plan {}", plan)).run();
+
+    assert!(
+        result.is_success(),
+        "{}",
+        result.failure_message("command to succeed")
+    );
+    assert!(
+        result
+            .stdout()
+            .contains(&format!("Wrote a plan to delete 3 refs to {}", plan)),
+        "{}",
+        result.failure_message("command to plan test1, origin/test1 and test2")
+    );
+
+    let json = fs::read_to_string(plan).unwrap();
+
+    assert!(
+        json.contains(r#""location": "origin","#)
+            && json.contains(r#""ref": "refs/heads/test1","#)
+            && json.contains(r#""reason": "merged into main""#),
+        "Expected the plan to list origin/test1, instead found\n{}",
+        json
+    );
+
+    project.batch_setup_commands(&[
+        "git checkout test2",
+        "git commit --allow-empty -m Moved",
+        "git checkout main",
+    ]);
+
+    let apply = project.git_clean_command(&format!("apply {}", plan)).run();
+
+    assert!(
+        apply.is_success(),
+        "{}",
+        apply.failure_message("command to succeed")
+    );
+    assert!(
+        apply.stdout().contains("Deleted branch test1")
+            && apply.stdout().contains(" - [deleted]         test1"),
+        "{}",
+        apply.failure_message("command to delete test1 locally and remotely")
+    );
+    assert!(
+        apply.stdout().contains("Skipped test2, it moved from ")
+            && !apply.stdout().contains("Deleted branch test2"),
+        "{}",
+        apply.failure_message("command to skip test2, which moved")
+    );
+
+    let again = project.git_clean_command(&format!("apply {}", plan)).run();
+
+    assert!(
+        again.stdout().contains("Skipped test1, it's already gone.")
+            && again
+                .stdout()
+                .contains("Nothing left to delete from the plan."),
+        "{}",
+        again.failure_message("command to skip everything the second time")
+    );
+}
+
+#[test]
+fn test_git_clean_rejects_invalid_plans() {
+    let project = project("git-clean_rejects_invalid_plans").build();
+
+    fs::write(project.path().join("plan.json"), "{\"branches\": []}").unwrap();
+
+    let result = project
+        .git_clean_command(&format!(
+            "apply {}",
+            project.path().join("plan.json").to_str().unwrap()
+        ))
+        .run();
+
+    assert!(
+        !result.is_success() && result.stdout().contains("Couldn't read the plan "),
+        "{}",
+        result.failure_message("command to reject the plan")
+    );
+}
@@ -1,3 +1,4 @@
+use std::fs;
 use support::project;
 
 #[test]
@@ -96,6 +97,493 @@ fn test_git_clean_does_not_remove_ignored_remote_branches() {
     );
 }
 
+#[test]
+fn test_git_clean_removes_squash_merged_branches_with_gone_upstreams() {
+    let project = project("git-clean_squashed_gone_upstreams")
+        .build()
+        .setup_remote();
+
+    // The usual Github flow: the PR branch is squash-merged, and its branch deleted on the remote.
+    project.batch_setup_commands(&[
+        "git checkout -b feat",
+        "git commit --allow-empty -m Feature",
+        "git push -u origin feat",
+        "git checkout main",
+        "git merge --squash feat",
+        "git commit --allow-empty -m Squashed",
+        "git push origin main",
+        "git push origin --delete feat",
+    ]);
+
+    let result = project.git_clean_command("-y -l --gone --squashes").run();
+
+    assert_eq!(
+        Some(0),
+        result.exit_code(),
+        "{}",
+        result.failure_message("command to succeed")
+    );
+    assert!(
+        !result.stdout().contains("Refusing to delete feat"),
+        "{}",
+        result.failure_message("command not to refuse feat")
+    );
+    assert!(
+        result.stdout().contains("Deleted branch feat"),
+        "{}",
+        result.failure_message("command to delete feat")
+    );
+}
+
+#[test]
+fn test_git_clean_removes_branches_with_gone_upstreams() {
+    let project = project("git-clean_gone_upstreams").build().setup_remote();
+
+    // `renamed` tracks `origin/upstream_name`, and there's an unrelated `origin/renamed` that has
+    // to survive.
+    project.batch_setup_commands(&[
+        "git checkout -b gone",
+        "git commit --allow-empty -m Gone",
+        "git push -u origin gone",
+        "git push origin --delete gone",
+        "git checkout -b renamed",
+        "git push -u origin renamed:upstream_name",
+        "git push origin renamed:renamed",
+        "git push origin --delete upstream_name",
+        "git checkout -b tracked",
+        "git commit --allow-empty -m Tracked",
+        "git push -u origin tracked",
+        "git checkout main",
+    ]);
+
+    let result = project.git_clean_command("-y --gone").run();
+
+    assert!(
+        result.is_success(),
+        "{}",
+        result.failure_message("command to succeed")
+    );
+    assert!(
+        result.stdout().contains("Deleted branch gone"),
+        "{}",
+        result.failure_message("command to delete gone")
+    );
+    assert!(
+        result.stdout().contains("Deleted branch renamed"),
+        "{}",
+        result.failure_message("command to delete renamed")
+    );
+    assert!(
+        !result
+            .stdout()
+            .contains(deleted_branch_output("renamed").as_str()),
+        "{}",
+        result.failure_message("command not to delete the unrelated remote renamed")
+    );
+    assert!(
+        !result.stdout().contains("Deleted branch tracked"),
+        "{}",
+        result.failure_message("command not to delete tracked")
+    );
+}
+
+#[test]
+fn test_git_clean_detects_the_base_branch_from_the_remote() {
+    let project = project("git-clean_detects_base_branch")
+        .build()
+        .setup_remote();
+
+    project.batch_setup_commands(&[
+        "git checkout -b develop",
+        "git push origin HEAD",
+        "git branch merged",
+    ]);
+    project.remote_setup_command("git symbolic-ref HEAD refs/heads/develop");
+
+    let result = project.git_clean_command("-y -l").run();
+
+    assert!(
+        result.is_success(),
+        "{}",
+        result.failure_message("command to succeed")
+    );
+    assert!(
+        result.stdout().contains("Deleted branch merged"),
+        "{}",
+        result.failure_message("command to delete merged")
+    );
+}
+
+#[test]
+fn test_git_clean_reports_a_missing_base_branch() {
+    let project = project("git-clean_reports_base_branch")
+        .build()
+        .setup_remote();
+
+    project.setup_command("git symbolic-ref refs/remotes/origin/HEAD refs/remotes/origin/trunk");
+
+    let result = project.git_clean_command("-y").run();
+
+    assert!(
+        !result.is_success(),
+        "{}",
+        result.failure_message("command to fail")
+    );
+    assert!(
+        result
+            .stdout()
+            .contains("Couldn't find the base branch trunk, locally or on the remote"),
+        "{}",
+        result.failure_message("command to report trunk as the base branch")
+    );
+}
+
+#[test]
+fn test_git_clean_uses_the_remote_base_branch_when_missing_locally() {
+    let project = project("git-clean_remote_base_branch")
+        .build()
+        .setup_remote();
+
+    project.batch_setup_commands(&[
+        "git checkout -b merged",
+        "git commit --allow-empty -m Merged",
+        "git push origin merged:main",
+        "git checkout -b work",
+        "git branch -D main",
+    ]);
+
+    let result = project.git_clean_command("-y -l -b main").run();
+
+    assert!(
+        result.is_success(),
+        "{}",
+        result.failure_message("command to succeed")
+    );
+    assert!(
+        result.stdout().contains("Deleted branch merged"),
+        "{}",
+        result.failure_message("command to delete merged")
+    );
+}
+
+#[test]
+fn test_git_clean_sweeps_merged_remote_branches() {
+    let project = project("git-clean_sweeps_remote_branches")
+        .build()
+        .setup_remote();
+
+    project.batch_setup_commands(&[
+        "git push origin main:swept",
+        "git push origin main:kept",
+        "git checkout -b unmerged",
+        "git commit --allow-empty -m Unmerged",
+        "git push origin HEAD",
+        "git checkout main",
+        "git branch -D unmerged",
+    ]);
+
+    let without_sweep = project.git_clean_command("-y -i kept").run();
+
+    assert_eq!(
+        Some(2),
+        without_sweep.exit_code(),
+        "{}",
+        without_sweep.failure_message("command to have nothing to clean")
+    );
+    assert!(
+        !without_sweep
+            .stdout()
+            .contains(deleted_branch_output("swept").as_str()),
+        "{}",
+        without_sweep.failure_message("command not to delete swept without --sweep-remotes")
+    );
+
+    let result = project
+        .git_clean_command("-y -i kept --sweep-remotes")
+        .run();
+
+    assert!(
+        result.is_success(),
+        "{}",
+        result.failure_message("command to succeed")
+    );
+    assert!(
+        result
+            .stdout()
+            .contains(deleted_branch_output("swept").as_str()),
+        "{}",
+        result.failure_message("command to delete swept")
+    );
+    assert!(
+        !result
+            .stdout()
+            .contains(deleted_branch_output("kept").as_str()),
+        "{}",
+        result.failure_message("command not to delete the ignored kept")
+    );
+    assert!(
+        !result
+            .stdout()
+            .contains(deleted_branch_output("unmerged").as_str()),
+        "{}",
+        result.failure_message("command not to delete unmerged")
+    );
+    assert!(
+        !result
+            .stdout()
+            .contains(deleted_branch_output("main").as_str()),
+        "{}",
+        result.failure_message("command not to delete main")
+    );
+}
+
+#[test]
+fn test_git_clean_only_sweeps_my_remote_branches() {
+    let project = project("git-clean_sweeps_my_branches")
+        .build()
+        .setup_remote();
+
+    project.setup_command("git commit --allow-empty -m Mine");
+    project.setup_command("git push origin main:mine");
+    project.setup_command_with_env(
+        "git commit --allow-empty -m Theirs",
+        &[("GIT_AUTHOR_EMAIL", "other@example.com")],
+    );
+    project.setup_command("git push origin main:theirs");
+    project.setup_command_with_env(
+        "git commit --allow-empty -m Mapped",
+        &[("GIT_AUTHOR_EMAIL", "old@example.com")],
+    );
+    project.batch_setup_commands(&["git push origin main:mapped", "git push origin main"]);
+
+    fs::write(
+        project.path().join(".mailmap"),
+        "agent <agent@local> <old@example.com>\n",
+    )
+    .unwrap();
+
+    let result = project.git_clean_command("-y --sweep-remotes --mine").run();
+
+    assert!(
+        result.is_success(),
+        "{}",
+        result.failure_message("command to succeed")
+    );
+    assert!(
+        result
+            .stdout()
+            .contains(deleted_branch_output("mine").as_str()),
+        "{}",
+        result.failure_message("command to delete mine")
+    );
+    assert!(
+        result
+            .stdout()
+            .contains(deleted_branch_output("mapped").as_str()),
+        "{}",
+        result.failure_message("command to delete mapped, which .mailmap says is mine")
+    );
+    assert!(
+        !result
+            .stdout()
+            .contains(deleted_branch_output("theirs").as_str()),
+        "{}",
+        result.failure_message("command not to delete theirs")
+    );
+}
+
+#[test]
+fn test_git_clean_does_not_remove_remote_branches_matching_ignored_patterns() {
+    let project = project("git-clean_ignores_remote_patterns")
+        .build()
+        .setup_remote();
+
+    project.batch_setup_commands(&[
+        "git push origin main:release/1.0",
+        "git push origin main:swept",
+        "git checkout -b local-fix",
+        "git push -u origin local-fix:release/1.1",
+        "git checkout main",
+    ]);
+
+    let result = project
+        .git_clean_command("-y -i release/* --sweep-remotes")
+        .run();
+
+    assert!(
+        result.is_success(),
+        "{}",
+        result.failure_message("command to succeed")
+    );
+    assert!(
+        result.stdout().contains("Deleted branch local-fix"),
+        "{}",
+        result.failure_message("command to delete local-fix locally")
+    );
+    assert!(
+        !result.stdout().contains("release/1.1"),
+        "{}",
+        result.failure_message("command not to delete the ignored upstream release/1.1")
+    );
+    assert!(
+        !result.stdout().contains("release/1.0"),
+        "{}",
+        result.failure_message("command not to sweep the ignored release/1.0")
+    );
+    assert!(
+        result
+            .stdout()
+            .contains(deleted_branch_output("swept").as_str()),
+        "{}",
+        result.failure_message("command to delete swept")
+    );
+}
+
+#[test]
+fn test_git_clean_skips_remote_branches_pushed_to_since_the_fetch() {
+    let project = project("git-clean_leases_remote_deletion")
+        .build()
+        .setup_remote();
+
+    project.batch_setup_commands(&[
+        "git checkout -b test1",
+        "git push origin HEAD",
+        "git checkout -b test2",
+        "git push origin HEAD",
+        "git checkout main",
+    ]);
+
+    // Fetch from a mirror that's missing the colleague's push below, while pushing to the real
+    // remote, so the remote-tracking ref is stale when the deletion is pushed.
+    let remote_url = project.setup_command("git remote get-url origin");
+    let remote_url = remote_url.stdout().trim();
+    let mirror = project.path().join("mirror.git");
+    let mirror = mirror.to_str().unwrap();
+    project.batch_setup_commands(&[
+        &format!("git clone --mirror {} {}", remote_url, mirror),
+        "git checkout -b colleague",
+        "git commit --allow-empty -m Theirs",
+        "git push origin colleague:test1",
+        "git checkout main",
+        "git branch -D colleague",
+        &format!("git remote set-url origin {}", mirror),
+        &format!("git remote set-url --push origin {}", remote_url),
+    ]);
+
+    let result = project.git_clean_command("-y").run();
+
+    assert_eq!(
+        Some(4),
+        result.exit_code(),
+        "{}",
+        result.failure_message("command to report a partial failure")
+    );
+    assert!(
+        result
+            .stdout()
+            .contains("origin/test1 was skipped, someone pushed to it since it was fetched."),
+        "{}",
+        result.failure_message("command to skip test1")
+    );
+    assert!(
+        result
+            .stdout()
+            .contains(deleted_branch_output("test2").as_str()),
+        "{}",
+        result.failure_message("command to delete test2")
+    );
+
+    let remote_branches = project.setup_command(&format!("git ls-remote --heads {}", remote_url));
+
+    assert!(
+        remote_branches.stdout().contains("refs/heads/test1"),
+        "{}",
+        remote_branches.failure_message("test1 to still be on the remote")
+    );
+}
+
 fn deleted_branch_output(branch: &str) -> String {
     format!(" - [deleted]         {}", branch)
 }
+
+#[test]
+fn test_git_clean_reports_failed_remote_deletions() {
+    let project = project("git-clean_failed_remote_deletion")
+        .build()
+        .setup_remote();
+
+    project.batch_setup_commands(&[
+        "git checkout -b merged",
+        "git push origin HEAD",
+        "git checkout main",
+        "git remote set-url origin does-not-exist",
+    ]);
+
+    let result = project.git_clean_command("-y -r").run();
+
+    assert_eq!(
+        Some(4),
+        result.exit_code(),
+        "{}",
+        result.failure_message("command to report a partial failure")
+    );
+    assert!(
+        result.stdout().contains(
+            "Couldn't delete origin/merged: `git push --porcelain --force-with-lease=refs/heads/merged:"
+        ),
+        "{}",
+        result.failure_message("the failed push to be reported")
+    );
+    assert!(
+        result
+            .stdout()
+            .contains("fatal: 'does-not-exist' does not appear to be a git repository"),
+        "{}",
+        result.failure_message("git's own error to be shown")
+    );
+    assert!(
+        !result.stdout().contains("panicked"),
+        "{}",
+        result.failure_message("no panic")
+    );
+}
+
+#[test]
+fn test_git_clean_sweeps_stale_merged_remote_branches_once() {
+    let project = project("git-clean_sweeps_stale_remote_branches")
+        .build()
+        .setup_remote();
+
+    project.setup_command("git checkout -b oldmerged");
+    project.setup_command_with_env(
+        "git commit --allow-empty -m Old",
+        &[("GIT_COMMITTER_DATE", "2000-01-01T00:00:00")],
+    );
+    project.batch_setup_commands(&[
+        "git push origin HEAD",
+        "git checkout main",
+        "git merge --ff-only oldmerged",
+        "git push origin main",
+        "git branch -D oldmerged",
+    ]);
+
+    let result = project
+        .git_clean_command("-y -r --sweep-remotes --older-than 90d")
+        .run();
+
+    assert_eq!(
+        Some(0),
+        result.exit_code(),
+        "{}",
+        result.failure_message("command to succeed")
+    );
+    assert_eq!(
+        1,
+        result
+            .stdout()
+            .matches(deleted_branch_output("oldmerged").as_str())
+            .count(),
+        "{}",
+        result.failure_message("command to delete oldmerged once")
+    );
+}
@@ -1,5 +1,6 @@
+use std::io::Write;
 use std::path::{Path, PathBuf};
-use std::process::{Command, Output};
+use std::process::{Command, Output, Stdio};
 use std::{env, str};
 use tempdir::TempDir;
 
@@ -58,19 +59,31 @@ impl ProjectBuilder {
 
 pub struct Project {
     directory: TempDir,
+    #[allow(dead_code)]
     pub name: String,
     remote: TempDir,
 }
 
 impl Project {
     pub fn setup_command(&self, command: &str) -> TestCommandResult {
+        self.setup_command_with_env(command, &[])
+    }
+
+    pub fn setup_command_with_env(
+        &self,
+        command: &str,
+        envs: &[(&str, &str)],
+    ) -> TestCommandResult {
         let command_pieces = command.split(' ').collect::<Vec<&str>>();
-        let result = TestCommand::new(
+        let mut test_command = TestCommand::new(
             &self.path(),
             command_pieces[1..].to_vec(),
             command_pieces[0],
-        )
-        .run();
+        );
+        for &(key, value) in envs {
+            test_command = test_command.env(key, value);
+        }
+        let result = test_command.run();
 
         if !result.is_success() {
             panic!("{}", result.failure_message("setup command to succeed"))
@@ -135,6 +148,7 @@ pub struct TestCommand {
     pub path: PathBuf,
     args: Vec<String>,
     envs: Vec<(String, String)>,
+    stdin: Option<String>,
     top_level_command: String,
 }
 
@@ -149,6 +163,7 @@ impl TestCommand {
             path: path.into(),
             args: owned_args,
             envs: vec![],
+            stdin: None,
             top_level_command: top_level_command.into(),
         }
     }
@@ -158,18 +173,38 @@ impl TestCommand {
         self
     }
 
+    pub fn stdin(mut self, input: &str) -> TestCommand {
+        self.stdin = Some(input.into());
+        self
+    }
+
     pub fn run(&self) -> TestCommandResult {
         let mut command = Command::new(&self.top_level_command);
-        for &(ref k, ref v) in &self.envs {
-            command.env(&k, &v);
+        for (k, v) in &self.envs {
+            command.env(k, v);
         }
-        let output = command
-            .args(&self.args)
-            .current_dir(&self.path)
-            .output()
-            .unwrap();
+        command.args(&self.args).current_dir(&self.path);
+
+        let output = match self.stdin {
+            Some(ref input) => {
+                let mut child = command
+                    .stdin(Stdio::piped())
+                    .stdout(Stdio::piped())
+                    .stderr(Stdio::piped())
+                    .spawn()
+                    .unwrap();
+                child
+                    .stdin
+                    .take()
+                    .unwrap()
+                    .write_all(input.as_bytes())
+                    .unwrap();
+                child.wait_with_output().unwrap()
+            }
+            None => command.output().unwrap(),
+        };
 
-        TestCommandResult { output: output }
+        TestCommandResult { output }
     }
 }
 
@@ -182,6 +217,10 @@ impl TestCommandResult {
         self.output.status.success()
     }
 
+    pub fn exit_code(&self) -> Option<i32> {
+        self.output.status.code()
+    }
+
     pub fn stdout(&self) -> &str {
         str::from_utf8(&self.output.stdout).unwrap()
     }
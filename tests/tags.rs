@@ -0,0 +1,189 @@
+use support::project;
+
+#[test]
+fn test_git_clean_removes_tags_already_in_a_release() {
+    let project = project("git-clean_removes_released_tags").build();
+
+    project.batch_setup_commands(&[
+        "git commit --allow-empty -m First",
+        "git tag rc-1",
+        "git commit --allow-empty -m Release",
+        "git tag -a v1.0 -m Release",
+        "git commit --allow-empty -m Second",
+        "git tag rc-2",
+    ]);
+
+    let result = project
+        .git_clean_command("-y -l --tags rc-* --release-tags v*")
+        .run();
+
+    assert!(
+        result.is_success(),
+        "{}",
+        result.failure_message("command to succeed")
+    );
+    assert!(
+        result.stdout().contains("Deleted tag 'rc-1'"),
+        "{}",
+        result.failure_message("command to delete rc-1")
+    );
+    assert!(
+        !result.stdout().contains("Deleted tag 'rc-2'"),
+        "{}",
+        result.failure_message("command not to delete the unreleased rc-2")
+    );
+    assert!(
+        !result.stdout().contains("Deleted tag 'v1.0'"),
+        "{}",
+        result.failure_message("command not to delete the release v1.0")
+    );
+}
+
+#[test]
+fn test_git_clean_removes_old_tags_locally_and_remotely() {
+    let project = project("git-clean_removes_old_tags").build().setup_remote();
+
+    project.setup_command_with_env(
+        "git tag -a ci-build-1 -m Build",
+        &[("GIT_COMMITTER_DATE", "2000-01-01T00:00:00")],
+    );
+    project.batch_setup_commands(&["git tag -a ci-build-2 -m Build", "git push origin --tags"]);
+
+    let result = project
+        .git_clean_command("--tags ci-build-* --older-than 30d")
+        .stdin("y\n")
+        .run();
+
+    assert!(
+        result.is_success(),
+        "{}",
+        result.failure_message("command to succeed")
+    );
+    assert!(
+        result.stdout().contains(
+            "The following tags will be deleted locally and remotely:\n\nCreated over 30 days ago, may hold unmerged work:\nci-build-1 ("
+        ),
+        "{}",
+        result.failure_message("command to list ci-build-1")
+    );
+    assert!(
+        result.stdout().contains("Deleted tag 'ci-build-1'"),
+        "{}",
+        result.failure_message("command to delete ci-build-1 locally")
+    );
+    assert!(
+        result.stdout().contains(" - [deleted]         ci-build-1"),
+        "{}",
+        result.failure_message("command to delete ci-build-1 remotely")
+    );
+    assert!(
+        !result.stdout().contains("ci-build-2"),
+        "{}",
+        result.failure_message("command not to delete ci-build-2")
+    );
+
+    let remote_tags = project.setup_command("git ls-remote --tags origin");
+
+    assert!(
+        !remote_tags.stdout().contains("ci-build-1"),
+        "{}",
+        remote_tags.failure_message("ci-build-1 to be gone from the remote")
+    );
+}
+
+#[test]
+fn test_git_clean_needs_a_reason_to_remove_tags() {
+    let project = project("git-clean_needs_tag_criteria").build();
+
+    project.setup_command("git tag ci-build-1");
+
+    let result = project.git_clean_command("-y --tags ci-build-*").run();
+
+    assert!(
+        !result.is_success(),
+        "{}",
+        result.failure_message("command to fail without --older-than or --release-tags")
+    );
+}
+
+#[test]
+fn test_git_clean_cleans_tags_that_differ_on_the_remote() {
+    let project = project("git-clean_differing_tags").build().setup_remote();
+
+    project.batch_setup_commands(&[
+        "git commit --allow-empty -m First",
+        "git tag rc-1",
+        "git push origin rc-1",
+        "git tag -d rc-1",
+        "git commit --allow-empty -m Release",
+        "git tag rc-1",
+        "git tag v1.0",
+    ]);
+
+    let result = project
+        .git_clean_command("-y --tags rc-* --release-tags v*")
+        .run();
+
+    assert_eq!(
+        Some(0),
+        result.exit_code(),
+        "{}",
+        result.failure_message("command to succeed")
+    );
+    assert!(
+        result.stdout().contains("Deleted tag 'rc-1'"),
+        "{}",
+        result.failure_message("command to delete rc-1")
+    );
+    assert!(
+        result
+            .stdout()
+            .contains("Keeping rc-1 on origin, it points to "),
+        "{}",
+        result.failure_message("command to report the differing rc-1")
+    );
+    assert!(
+        !result.stdout().contains(" - [deleted]         rc-1"),
+        "{}",
+        result.failure_message("command not to delete the remote rc-1")
+    );
+
+    let remote_tags = project.setup_command("git ls-remote --tags origin");
+
+    assert!(
+        remote_tags.stdout().contains("refs/tags/rc-1"),
+        "{}",
+        remote_tags.failure_message("rc-1 to still be on the remote")
+    );
+}
+
+#[test]
+fn test_git_clean_leaves_tags_only_on_the_remote_alone() {
+    let project = project("git-clean_remote_only_tags").build().setup_remote();
+
+    project.batch_setup_commands(&[
+        "git tag rc-1",
+        "git push origin rc-1",
+        "git tag -d rc-1",
+        "git tag v1.0",
+    ]);
+
+    let result = project
+        .git_clean_command("-y -r --tags rc-* --release-tags v*")
+        .run();
+
+    assert_eq!(
+        Some(2),
+        result.exit_code(),
+        "{}",
+        result.failure_message("command to have nothing to clean")
+    );
+
+    let remote_tags = project.setup_command("git ls-remote --tags origin");
+
+    assert!(
+        remote_tags.stdout().contains("refs/tags/rc-1"),
+        "{}",
+        remote_tags.failure_message("rc-1 to still be on the remote")
+    );
+}
@@ -6,7 +6,12 @@ extern crate tempdir;
 mod support;
 
 // Actual integration tests
+mod archive;
+mod bundle;
 mod deletion;
 mod local;
+mod plan;
 mod remote;
+mod tags;
+mod undo;
 mod utility;
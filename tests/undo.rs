@@ -0,0 +1,172 @@
+use support::project;
+
+#[test]
+fn test_git_clean_undoes_the_latest_run() {
+    let project = project("git-clean_undoes_latest_run")
+        .build()
+        .setup_remote();
+
+    project.batch_setup_commands(&[
+        "git checkout -b test1",
+        "git push origin HEAD",
+        "git checkout main",
+        "git branch test2",
+        "git tag ci-build-1",
+        "git tag v1.0",
+    ]);
+
+    let clean = project.git_clean_command("-y").run();
+
+    assert!(
+        clean.is_success(),
+        "{}",
+        clean.failure_message("command to succeed")
+    );
+
+    let tags = project
+        .git_clean_command("-y -l --tags ci-build-* --release-tags v*")
+        .run();
+
+    assert!(
+        tags.stdout().contains("Deleted tag 'ci-build-1'"),
+        "{}",
+        tags.failure_message("command to delete ci-build-1")
+    );
+
+    let list = project.git_clean_command("undo --list").run();
+    let runs = list.stdout().lines().collect::<Vec<&str>>();
+
+    assert!(
+        runs.len() == 2
+            && runs[0].ends_with(": tag ci-build-1")
+            && runs[1].ends_with(": origin/test1, test1, test2"),
+        "{}",
+        list.failure_message("both runs to be listed, latest first")
+    );
+
+    let undo_tags = project.git_clean_command("undo").run();
+
+    assert!(
+        undo_tags.stdout().contains("Restored tag ci-build-1 (was "),
+        "{}",
+        undo_tags.failure_message("command to restore ci-build-1")
+    );
+
+    let run = runs[1].split(':').next().unwrap();
+    let undo = project.git_clean_command(&format!("undo {}", run)).run();
+
+    assert!(
+        undo.is_success(),
+        "{}",
+        undo.failure_message("command to succeed")
+    );
+    assert!(
+        undo.stdout().contains("Restored test1 (was ")
+            && undo.stdout().contains("Restored test2 (was "),
+        "{}",
+        undo.failure_message("command to restore test1 and test2")
+    );
+    assert!(
+        undo.stdout().contains("Restored origin/test1 (was "),
+        "{}",
+        undo.failure_message("command to push test1 back")
+    );
+
+    let branches = project.setup_command("git branch");
+    let remote_branches = project.setup_command("git ls-remote --heads origin");
+    let tags = project.setup_command("git tag");
+
+    assert!(
+        branches.stdout().contains("test1") && branches.stdout().contains("test2"),
+        "{}",
+        branches.failure_message("test1 and test2 to be back")
+    );
+    assert!(
+        remote_branches.stdout().contains("refs/heads/test1"),
+        "{}",
+        remote_branches.failure_message("test1 to be back on the remote")
+    );
+    assert!(
+        tags.stdout().contains("ci-build-1"),
+        "{}",
+        tags.failure_message("ci-build-1 to be back")
+    );
+}
+
+#[test]
+fn test_git_clean_undo_leaves_recreated_remote_branches_alone() {
+    let project = project("git-clean_undo_recreated_remote")
+        .build()
+        .setup_remote();
+
+    project.batch_setup_commands(&[
+        "git commit --allow-empty -m Second",
+        "git checkout -b test1",
+        "git push origin HEAD",
+        "git checkout main",
+    ]);
+
+    let clean = project.git_clean_command("-y").run();
+
+    assert!(
+        clean.is_success(),
+        "{}",
+        clean.failure_message("command to succeed")
+    );
+
+    // Restoring test1 would fast-forward the branch someone recreated, so it has to be refused.
+    project.setup_command("git push origin main~1:refs/heads/test1");
+    let recreated = project.setup_command("git rev-parse main~1");
+
+    let undo = project.git_clean_command("undo").run();
+
+    assert!(
+        undo.is_success(),
+        "{}",
+        undo.failure_message("command to succeed")
+    );
+    assert!(
+        undo.stdout().contains("Restored test1 (was ")
+            && undo.stdout().contains(
+                "Couldn't restore origin/test1: someone recreated it since it was deleted"
+            ),
+        "{}",
+        undo.failure_message("command to restore test1 and leave origin/test1 alone")
+    );
+
+    let remote_branches = project.setup_command("git ls-remote --heads origin test1");
+
+    assert!(
+        remote_branches
+            .stdout()
+            .starts_with(recreated.stdout().trim()),
+        "{}",
+        remote_branches.failure_message("origin/test1 to still point where it was recreated")
+    );
+}
+
+#[test]
+fn test_git_clean_has_nothing_to_undo_at_first() {
+    let project = project("git-clean_nothing_to_undo").build();
+
+    let result = project.git_clean_command("undo").run();
+
+    assert!(
+        !result.is_success(),
+        "{}",
+        result.failure_message("command to fail")
+    );
+    assert!(
+        result.stdout().contains("There's nothing to undo"),
+        "{}",
+        result.failure_message("command to say there's nothing to undo")
+    );
+
+    let unknown = project.git_clean_command("undo 12345").run();
+
+    assert!(
+        unknown.stdout().contains("Couldn't find the run 12345"),
+        "{}",
+        unknown.failure_message("command to report the unknown run")
+    );
+}
@@ -19,3 +19,37 @@ fn test_git_clean_checks_for_git_in_path() {
         result.failure_message("to be missing the git command")
     );
 }
+
+#[test]
+fn test_git_clean_exit_codes() {
+    let project = project("git-clean_exit_codes").build();
+
+    let clean = project.git_clean_command("-y").run();
+
+    assert_eq!(
+        Some(2),
+        clean.exit_code(),
+        "{}",
+        clean.failure_message("command to have nothing to clean")
+    );
+
+    project.setup_command("git branch merged");
+
+    let aborted = project.git_clean_command("-l").stdin("n\n").run();
+
+    assert_eq!(
+        Some(3),
+        aborted.exit_code(),
+        "{}",
+        aborted.failure_message("command to be aborted")
+    );
+
+    let result = project.git_clean_command("-y -l").run();
+
+    assert_eq!(
+        Some(0),
+        result.exit_code(),
+        "{}",
+        result.failure_message("command to succeed")
+    );
+}